@@ -1 +1 @@
-rlp::Rlp::new(&[249, 7, 90, 1, 185, 7, 85, 249, 7, 82, 102, 1, 184, 216, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 121, 4, 169, 131, 62, 163, 218, 102, 170, 222, 163, 214, 3, 89, 252, 0, 125, 183, 199, 159, 174, 42, 70, 142, 117, 9, 135, 188, 118, 93, 80, 26, 61, 90, 169, 131, 62, 163, 218, 102, 170, 222, 163, 214, 3, 89, 252, 0, 125, 183, 199, 159, 174, 42, 70, 142, 117, 9, 135, 188, 118, 93, 80, 26, 61, 90, 118, 118, 7, 169, 117, 103, 15, 15, 245, 133, 191, 222, 200, 71, 147, 3, 218, 178, 247, 198, 102, 36, 143, 49, 178, 234, 167, 161, 51, 31, 253, 180, 33, 29, 223, 53, 18, 158, 116, 103, 254, 210, 94, 186, 208, 106, 22, 192, 149, 99, 156, 180, 190, 108, 221, 15, 241, 54, 178, 19, 20, 79, 189, 52, 0, 0, 1, 104, 163, 101, 246, 224, 199, 44, 10, 112, 13, 34, 161, 38, 0, 206, 8, 208, 8, 192, 203, 74, 141, 37, 87, 130, 115, 161, 114, 166, 237, 175, 13, 63, 45, 122, 138, 123, 43, 60, 204, 224, 97, 199, 144, 166, 255, 215, 33, 255, 146, 46, 116, 219, 36, 11, 242, 122, 142, 90, 119, 218, 144, 208, 180, 247, 225, 7, 174, 11, 249, 6, 114, 160, 191, 87, 201, 165, 103, 202, 180, 51, 68, 77, 14, 162, 1, 96, 247, 200, 160, 118, 88, 178, 207, 166, 17, 85, 82, 94, 7, 8, 240, 102, 77, 29, 160, 38, 29, 64, 8, 190, 159, 115, 49, 68, 61, 145, 231, 13, 37, 206, 203, 120, 248, 110, 122, 125, 32, 173, 220, 175, 185, 103, 120, 24, 46, 193, 114, 160, 236, 189, 177, 232, 146, 96, 197, 187, 135, 159, 29, 132, 49, 71, 178, 42, 9, 241, 68, 187, 11, 68, 232, 62, 61, 62, 52, 123, 202, 124, 244, 196, 160, 106, 144, 198, 15, 86, 104, 154, 194, 15, 223, 20, 9, 75, 181, 253, 212, 85, 172, 241, 82, 27, 95, 101, 106, 101, 205, 109, 90, 84, 77, 104, 236, 160, 140, 223, 141, 155, 63, 181, 196, 36, 238, 246, 55, 197, 14, 17, 105, 220, 39, 166, 99, 181, 216, 234, 5, 199, 84, 114, 105, 243, 141, 8, 7, 67, 160, 158, 90, 20, 201, 228, 178, 252, 134, 38, 35, 242, 21, 49, 173, 19, 203, 134, 121, 77, 60, 158, 235, 100, 226, 144, 125, 234, 73, 174, 192, 71, 30, 160, 199, 231, 34, 249, 110, 146, 191, 65, 176, 246, 244, 55, 192, 184, 12, 219, 177, 19, 211, 42, 228, 252, 44, 152, 182, 114, 134, 52, 105, 70, 251, 15, 160, 131, 66, 16, 97, 135, 178, 195, 180, 126, 253, 253, 130, 13, 8, 35, 181, 63, 208, 60, 34, 0, 84, 211, 103, 175, 139, 13, 5, 32, 138, 110, 81, 160, 150, 197, 0, 231, 6, 80, 206, 85, 109, 1, 230, 122, 162, 222, 66, 206, 251, 74, 125, 108, 195, 137, 123, 113, 159, 16, 14, 9, 50, 2, 110, 3, 160, 135, 143, 244, 223, 45, 122, 4, 83, 183, 235, 167, 33, 34, 36, 128, 14, 245, 36, 105, 126, 76, 97, 239, 73, 155, 58, 215, 203, 21, 181, 232, 151, 160, 19, 110, 105, 151, 161, 150, 197, 170, 41, 100, 18, 63, 76, 162, 27, 86, 94, 75, 158, 81, 1, 240, 158, 130, 166, 32, 125, 145, 232, 48, 221, 236, 160, 11, 206, 81, 71, 27, 98, 142, 197, 244, 255, 209, 198, 78, 227, 80, 240, 128, 197, 26, 31, 6, 234, 144, 83, 136, 167, 183, 72, 130, 196, 155, 247, 160, 177, 165, 120, 249, 140, 15, 215, 119, 34, 85, 142, 2, 189, 208, 127, 50, 123, 99, 60, 223, 228, 224, 74, 227, 188, 11, 155, 166, 44, 114, 93, 85, 160, 198, 248, 38, 11, 32, 20, 214, 189, 176, 69, 234, 67, 145, 114, 132, 111, 139, 28, 208, 3, 180, 135, 19, 164, 126, 166, 206, 26, 244, 90, 228, 197, 160, 177, 121, 160, 100, 28, 14, 197, 237, 232, 171, 60, 149, 245, 78, 28, 186, 183, 218, 70, 145, 171, 244, 20, 219, 75, 119, 248, 100, 212, 70, 189, 11, 160, 35, 58, 212, 239, 54, 199, 56, 205, 56, 235, 54, 112, 142, 167, 2, 52, 103, 123, 62, 5, 35, 79, 7, 159, 17, 243, 255, 153, 138, 26, 179, 10, 160, 143, 195, 114, 81, 156, 249, 157, 69, 176, 52, 149, 216, 138, 146, 159, 239, 228, 227, 249, 58, 23, 26, 5, 24, 51, 176, 70, 148, 192, 40, 7, 110, 160, 0, 244, 195, 208, 237, 54, 81, 225, 55, 86, 187, 152, 35, 93, 88, 165, 76, 197, 227, 126, 150, 168, 33, 33, 123, 152, 156, 176, 217, 59, 105, 54, 160, 230, 249, 119, 239, 233, 28, 9, 187, 30, 133, 37, 87, 154, 89, 8, 185, 40, 92, 150, 187, 253, 175, 139, 173, 129, 141, 210, 62, 10, 133, 4, 140, 160, 74, 147, 11, 19, 95, 68, 92, 200, 224, 238, 72, 168, 210, 143, 127, 218, 4, 71, 152, 142, 143, 113, 31, 224, 140, 163, 192, 162, 236, 232, 44, 180, 160, 71, 105, 172, 233, 26, 106, 67, 65, 237, 72, 127, 45, 241, 5, 200, 90, 61, 143, 111, 96, 249, 38, 232, 248, 159, 182, 174, 149, 251, 177, 82, 47, 160, 107, 2, 158, 34, 188, 97, 95, 150, 92, 253, 63, 51, 141, 250, 142, 226, 43, 15, 56, 227, 109, 109, 180, 80, 36, 250, 193, 248, 6, 10, 183, 209, 160, 75, 210, 252, 139, 239, 3, 156, 221, 141, 213, 254, 117, 236, 140, 233, 161, 92, 92, 220, 23, 180, 12, 101, 140, 236, 66, 23, 196, 174, 77, 246, 123, 160, 144, 195, 110, 44, 39, 221, 255, 247, 193, 168, 140, 51, 72, 227, 111, 102, 65, 26, 194, 89, 176, 162, 223, 3, 225, 165, 0, 52, 23, 109, 166, 65, 160, 117, 127, 247, 204, 67, 122, 142, 75, 42, 34, 252, 219, 19, 14, 86, 134, 209, 87, 68, 254, 180, 143, 201, 31, 18, 158, 206, 154, 243, 110, 211, 196, 160, 115, 43, 89, 206, 88, 43, 176, 98, 55, 186, 251, 51, 24, 38, 188, 172, 60, 78, 98, 98, 244, 80, 41, 14, 143, 95, 202, 132, 77, 188, 202, 251, 160, 170, 186, 38, 108, 238, 91, 125, 186, 62, 167, 137, 51, 35, 59, 126, 80, 121, 81, 162, 247, 65, 207, 112, 179, 25, 149, 73, 173, 38, 45, 45, 106, 160, 57, 122, 118, 168, 241, 139, 66, 86, 168, 124, 123, 83, 187, 220, 177, 140, 169, 195, 119, 206, 180, 135, 95, 239, 163, 204, 120, 153, 241, 226, 181, 230, 160, 232, 156, 124, 233, 37, 71, 231, 89, 245, 96, 124, 200, 201, 154, 211, 105, 113, 190, 160, 213, 129, 143, 104, 54, 169, 134, 223, 139, 138, 175, 64, 23, 160, 186, 180, 23, 60, 33, 72, 114, 188, 206, 206, 201, 66, 46, 84, 50, 118, 248, 129, 130, 155, 122, 13, 42, 225, 160, 44, 92, 103, 170, 151, 131, 237, 160, 227, 170, 31, 197, 169, 4, 62, 111, 62, 208, 227, 31, 114, 203, 139, 59, 114, 178, 213, 147, 76, 87, 121, 234, 44, 98, 135, 72, 195, 145, 228, 137, 160, 153, 248, 71, 152, 65, 121, 59, 158, 9, 29, 213, 181, 116, 98, 21, 162, 6, 191, 184, 248, 131, 59, 167, 236, 117, 33, 133, 164, 249, 202, 86, 157, 160, 121, 213, 221, 10, 133, 137, 58, 215, 118, 118, 130, 218, 118, 233, 251, 181, 206, 51, 116, 5, 8, 38, 150, 41, 50, 42, 40, 125, 153, 77, 141, 44, 160, 9, 233, 132, 64, 245, 29, 210, 229, 56, 174, 121, 229, 228, 112, 51, 118, 17, 24, 41, 159, 187, 83, 8, 146, 137, 72, 225, 209, 51, 254, 181, 76, 160, 108, 61, 114, 63, 98, 91, 220, 183, 98, 77, 184, 159, 216, 80, 181, 184, 65, 210, 1, 254, 245, 179, 57, 210, 198, 95, 66, 244, 20, 221, 233, 51, 160, 169, 50, 224, 195, 74, 171, 144, 100, 98, 94, 57, 191, 245, 36, 6, 54, 153, 169, 120, 202, 58, 198, 57, 19, 245, 125, 115, 146, 81, 202, 6, 207, 160, 141, 210, 123, 11, 10, 26, 108, 113, 123, 119, 81, 140, 108, 6, 74, 19, 137, 230, 187, 69, 143, 93, 209, 198, 65, 26, 88, 173, 30, 228, 38, 190, 160, 50, 215, 222, 74, 157, 187, 177, 219, 30, 87, 135, 94, 39, 47, 40, 8, 215, 244, 6, 18, 43, 29, 135, 223, 21, 49, 117, 20, 236, 110, 6, 9, 160, 37, 95, 1, 43, 143, 80, 85, 185, 194, 128, 253, 237, 28, 234, 75, 87, 128, 212, 22, 141, 50, 230, 216, 115, 253, 201, 146, 163, 155, 12, 129, 219, 160, 43, 141, 164, 45, 43, 82, 24, 161, 30, 59, 118, 182, 240, 104, 124, 109, 251, 62, 214, 10, 76, 77, 119, 192, 210, 145, 11, 19, 72, 91, 32, 137, 160, 91, 111, 76, 148, 28, 120, 206, 99, 76, 73, 118, 98, 158, 254, 20, 124, 227, 162, 138, 51, 71, 135, 54, 102, 219, 218, 184, 64, 63, 220, 122, 53, 160, 141, 252, 123, 242, 21, 241, 23, 245, 195, 5, 66, 142, 225, 248, 201, 161, 64, 82, 53, 66, 175, 223, 100, 97, 134, 177, 143, 19, 84, 33, 230, 252, 160, 232, 190, 89, 139, 117, 200, 149, 78, 163, 40, 15, 210, 88, 72, 67, 190, 208, 212, 235, 195, 183, 1, 126, 176, 208, 27, 184, 144, 190, 30, 7, 47, 160, 162, 213, 137, 63, 78, 206, 70, 205, 179, 204, 144, 70, 25, 93, 169, 176, 236, 26, 42, 84, 235, 221, 168, 159, 192, 204, 64, 58, 232, 1, 107, 92, 160, 210, 130, 42, 186, 181, 132, 36, 82, 130, 225, 205, 67, 148, 142, 166, 109, 81, 114, 68, 213, 110, 130, 67, 103, 72, 84, 147, 251, 136, 0, 137, 24, 160, 31, 174, 68, 26, 19, 112, 112, 45, 191, 75, 205, 20, 139, 26, 117, 125, 122, 82, 112, 210, 185, 190, 73, 20, 170, 185, 214, 46, 178, 112, 181, 74, 160, 146, 195, 183, 75, 21, 4, 242, 60, 22, 9, 215, 163, 98, 218, 252, 126, 239, 41, 44, 96, 224, 255, 206, 103, 54, 168, 98, 97, 57, 165, 69, 176, 160, 141, 119, 72, 74, 161, 198, 210, 74, 79, 3, 171, 23, 74, 15, 222, 227, 242, 79, 174, 220, 97, 228, 50, 25, 34, 12, 84, 42, 139, 237, 181, 231, 160, 24, 237, 222, 18, 73, 14, 164, 210, 227, 226, 3, 2, 88, 170, 247, 77, 215, 38, 229, 131, 247, 237, 86, 22, 131, 176, 87, 114, 146, 170, 149, 188, 160, 143, 70, 241, 22, 116, 183, 217, 56, 189, 154, 60, 98, 174, 45, 76, 80, 252, 141, 249, 210, 132, 8, 99, 220, 30, 72, 171, 129, 181, 66, 25, 120, 192, 1]);
+rlp::Rlp::new(&[249, 7, 90, 1, 185, 7, 85, 249, 7, 82, 102, 1, 184, 216, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 121, 4, 169, 131, 62, 163, 218, 102, 170, 222, 163, 214, 3, 89, 252, 0, 125, 183, 199, 159, 174, 42, 70, 142, 117, 9, 135, 188, 118, 93, 80, 26, 61, 90, 169, 131, 62, 163, 218, 102, 170, 222, 163, 214, 3, 89, 252, 0, 125, 183, 199, 159, 174, 42, 70, 142, 117, 9, 135, 188, 118, 93, 80, 26, 61, 90, 118, 118, 7, 169, 117, 103, 15, 15, 245, 133, 191, 222, 200, 71, 147, 3, 218, 178, 247, 198, 102, 36, 143, 49, 178, 234, 167, 161, 51, 31, 253, 180, 33, 29, 223, 53, 18, 158, 116, 103, 254, 210, 94, 186, 208, 106, 22, 192, 149, 99, 156, 180, 190, 108, 221, 15, 241, 54, 178, 19, 20, 79, 189, 52, 0, 0, 1, 104, 163, 101, 246, 224, 199, 44, 10, 112, 13, 34, 161, 38, 0, 206, 8, 208, 8, 192, 203, 74, 141, 37, 87, 130, 115, 161, 114, 166, 237, 175, 13, 63, 45, 122, 138, 123, 43, 60, 204, 224, 97, 199, 144, 166, 255, 215, 33, 255, 146, 46, 116, 219, 36, 11, 242, 122, 142, 90, 119, 218, 144, 208, 180, 247, 225, 7, 174, 11, 249, 6, 114, 160, 191, 87, 201, 165, 103, 202, 180, 51, 68, 77, 14, 162, 1, 96, 247, 200, 160, 118, 88, 178, 207, 166, 17, 85, 82, 94, 7, 8, 240, 102, 77, 29, 160, 38, 29, 64, 8, 190, 159, 115, 49, 68, 61, 145, 231, 13, 37, 206, 203, 120, 248, 110, 122, 125, 32, 173, 220, 175, 185, 103, 120, 24, 46, 193, 114, 160, 236, 189, 177, 232, 146, 96, 197, 187, 135, 159, 29, 132, 49, 71, 178, 42, 9, 241, 68, 187, 11, 68, 232, 62, 61, 62, 52, 123, 202, 124, 244, 196, 160, 106, 144, 198, 15, 86, 104, 154, 194, 15, 223, 20, 9, 75, 181, 253, 212, 85, 172, 241, 82, 27, 95, 101, 106, 101, 205, 109, 90, 84, 77, 104, 236, 160, 140, 223, 141, 155, 63, 181, 196, 36, 238, 246, 55, 197, 14, 17, 105, 220, 39, 166, 99, 181, 216, 234, 5, 199, 84, 114, 105, 243, 141, 8, 7, 67, 160, 158, 90, 20, 201, 228, 178, 252, 134, 38, 35, 242, 21, 49, 173, 19, 203, 134, 121, 77, 60, 158, 235, 100, 226, 144, 125, 234, 73, 174, 192, 71, 30, 160, 199, 231, 34, 249, 110, 146, 191, 65, 176, 246, 244, 55, 192, 184, 12, 219, 177, 19, 211, 42, 228, 252, 44, 152, 182, 114, 134, 52, 105, 70, 251, 15, 160, 131, 66, 16, 97, 135, 178, 195, 180, 126, 253, 253, 130, 13, 8, 35, 181, 63, 208, 60, 34, 0, 84, 211, 103, 175, 139, 13, 5, 32, 138, 110, 81, 160, 150, 197, 0, 231, 6, 80, 206, 85, 109, 1, 230, 122, 162, 222, 66, 206, 251, 74, 125, 108, 195, 137, 123, 113, 159, 16, 14, 9, 50, 2, 110, 3, 160, 135, 143, 244, 223, 45, 122, 4, 83, 183, 235, 167, 33, 34, 36, 128, 14, 245, 36, 105, 126, 76, 97, 239, 73, 155, 58, 215, 203, 21, 181, 232, 151, 160, 19, 110, 105, 151, 161, 150, 197, 170, 41, 100, 18, 63, 76, 162, 27, 86, 94, 75, 158, 81, 1, 240, 158, 130, 166, 32, 125, 145, 232, 48, 221, 236, 160, 11, 206, 81, 71, 27, 98, 142, 197, 244, 255, 209, 198, 78, 227, 80, 240, 128, 197, 26, 31, 6, 234, 144, 83, 136, 167, 183, 72, 130, 196, 155, 247, 160, 177, 165, 120, 249, 140, 15, 215, 119, 34, 85, 142, 2, 189, 208, 127, 50, 123, 99, 60, 223, 228, 224, 74, 227, 188, 11, 155, 166, 44, 114, 93, 85, 160, 198, 248, 38, 11, 32, 20, 214, 189, 176, 69, 234, 67, 145, 114, 132, 111, 139, 28, 208, 3, 180, 135, 19, 164, 126, 166, 206, 26, 244, 90, 228, 197, 160, 177, 121, 160, 100, 28, 14, 197, 237, 232, 171, 60, 149, 245, 78, 28, 186, 183, 218, 70, 145, 171, 244, 20, 219, 75, 119, 248, 100, 212, 70, 189, 11, 160, 35, 58, 212, 239, 54, 199, 56, 205, 56, 235, 54, 112, 142, 167, 2, 52, 103, 123, 62, 5, 35, 79, 7, 159, 17, 243, 255, 153, 138, 26, 179, 10, 160, 143, 195, 114, 81, 156, 249, 157, 69, 176, 52, 149, 216, 138, 146, 159, 239, 228, 227, 249, 58, 23, 26, 5, 24, 51, 176, 70, 148, 192, 40, 7, 110, 160, 0, 244, 195, 208, 237, 54, 81, 225, 55, 86, 187, 152, 35, 93, 88, 165, 76, 197, 227, 126, 150, 168, 33, 33, 123, 152, 156, 176, 217, 59, 105, 54, 160, 230, 249, 119, 239, 233, 28, 9, 187, 30, 133, 37, 87, 154, 89, 8, 185, 40, 92, 150, 187, 253, 175, 139, 173, 129, 141, 210, 62, 10, 133, 4, 140, 160, 74, 147, 11, 19, 95, 68, 92, 200, 224, 238, 72, 168, 210, 143, 127, 218, 4, 71, 152, 142, 143, 113, 31, 224, 140, 163, 192, 162, 236, 232, 44, 180, 160, 71, 105, 172, 233, 26, 106, 67, 65, 237, 72, 127, 45, 241, 5, 200, 90, 61, 143, 111, 96, 249, 38, 232, 248, 159, 182, 174, 149, 251, 177, 82, 47, 160, 107, 2, 158, 34, 188, 97, 95, 150, 92, 253, 63, 51, 141, 250, 142, 226, 43, 15, 56, 227, 109, 109, 180, 80, 36, 250, 193, 248, 6, 10, 183, 209, 160, 75, 210, 252, 139, 239, 3, 156, 221, 141, 213, 254, 117, 236, 140, 233, 161, 92, 92, 220, 23, 180, 12, 101, 140, 236, 66, 23, 196, 174, 77, 246, 123, 160, 144, 195, 110, 44, 39, 221, 255, 247, 193, 168, 140, 51, 72, 227, 111, 102, 65, 26, 194, 89, 176, 162, 223, 3, 225, 165, 0, 52, 23, 109, 166, 65, 160, 117, 127, 247, 204, 67, 122, 142, 75, 42, 34, 252, 219, 19, 14, 86, 134, 209, 87, 68, 254, 180, 143, 201, 31, 18, 158, 206, 154, 243, 110, 211, 196, 160, 115, 43, 89, 206, 88, 43, 176, 98, 55, 186, 251, 51, 24, 38, 188, 172, 60, 78, 98, 98, 244, 80, 41, 14, 143, 95, 202, 132, 77, 188, 202, 251, 160, 170, 186, 38, 108, 238, 91, 125, 186, 62, 167, 137, 51, 35, 59, 126, 80, 121, 81, 162, 247, 65, 207, 112, 179, 25, 149, 73, 173, 38, 45, 45, 106, 160, 57, 122, 118, 168, 241, 139, 66, 86, 168, 124, 123, 83, 187, 220, 177, 140, 169, 195, 119, 206, 180, 135, 95, 239, 163, 204, 120, 153, 241, 226, 181, 230, 160, 232, 156, 124, 233, 37, 71, 231, 89, 245, 96, 124, 200, 201, 154, 211, 105, 113, 190, 160, 213, 129, 143, 104, 54, 169, 134, 223, 139, 138, 175, 64, 23, 160, 186, 180, 23, 60, 33, 72, 114, 188, 206, 206, 201, 66, 46, 84, 50, 118, 248, 129, 130, 155, 122, 13, 42, 225, 160, 44, 92, 103, 170, 151, 131, 237, 160, 227, 170, 31, 197, 169, 4, 62, 111, 62, 208, 227, 31, 114, 203, 139, 59, 114, 178, 213, 147, 76, 87, 121, 234, 44, 98, 135, 72, 195, 145, 228, 137, 160, 153, 248, 71, 152, 65, 121, 59, 158, 9, 29, 213, 181, 116, 98, 21, 162, 6, 191, 184, 248, 131, 59, 167, 236, 117, 33, 133, 164, 249, 202, 86, 157, 160, 121, 213, 221, 10, 133, 137, 58, 215, 118, 118, 130, 218, 118, 233, 251, 181, 206, 51, 116, 5, 8, 38, 150, 41, 50, 42, 40, 125, 153, 77, 141, 44, 160, 9, 233, 132, 64, 245, 29, 210, 229, 56, 174, 121, 229, 228, 112, 51, 118, 17, 24, 41, 159, 187, 83, 8, 146, 137, 72, 225, 209, 51, 254, 181, 76, 160, 108, 61, 114, 63, 98, 91, 220, 183, 98, 77, 184, 159, 216, 80, 181, 184, 65, 210, 1, 254, 245, 179, 57, 210, 198, 95, 66, 244, 20, 221, 233, 51, 160, 169, 50, 224, 195, 74, 171, 144, 100, 98, 94, 57, 191, 245, 36, 6, 54, 153, 169, 120, 202, 58, 198, 57, 19, 245, 125, 115, 146, 81, 202, 6, 207, 160, 141, 210, 123, 11, 10, 26, 108, 113, 123, 119, 81, 140, 108, 6, 74, 19, 137, 230, 187, 69, 143, 93, 209, 198, 65, 26, 88, 173, 30, 228, 38, 190, 160, 50, 215, 222, 74, 157, 187, 177, 219, 30, 87, 135, 94, 39, 47, 40, 8, 215, 244, 6, 18, 43, 29, 135, 223, 21, 49, 117, 20, 236, 110, 6, 9, 160, 37, 95, 1, 43, 143, 80, 85, 185, 194, 128, 253, 237, 28, 234, 75, 87, 128, 212, 22, 141, 50, 230, 216, 115, 253, 201, 146, 163, 155, 12, 129, 219, 160, 43, 141, 164, 45, 43, 82, 24, 161, 30, 59, 118, 182, 240, 104, 124, 109, 251, 62, 214, 10, 76, 77, 119, 192, 210, 145, 11, 19, 72, 91, 32, 137, 160, 91, 111, 76, 148, 28, 120, 206, 99, 76, 73, 118, 98, 158, 254, 20, 124, 227, 162, 138, 51, 71, 135, 54, 102, 219, 218, 184, 64, 63, 220, 122, 53, 160, 141, 252, 123, 242, 21, 241, 23, 245, 195, 5, 66, 142, 225, 248, 201, 161, 64, 82, 53, 66, 175, 223, 100, 97, 134, 177, 143, 19, 84, 33, 230, 252, 160, 232, 190, 89, 139, 117, 200, 149, 78, 163, 40, 15, 210, 88, 72, 67, 190, 208, 212, 235, 195, 183, 1, 126, 176, 208, 27, 184, 144, 190, 30, 7, 47, 160, 162, 213, 137, 63, 78, 206, 70, 205, 179, 204, 144, 70, 25, 93, 169, 176, 236, 26, 42, 84, 235, 221, 168, 159, 192, 204, 64, 58, 232, 1, 107, 92, 160, 210, 130, 42, 186, 181, 132, 36, 82, 130, 225, 205, 67, 148, 142, 166, 109, 81, 114, 68, 213, 110, 130, 67, 103, 72, 84, 147, 251, 136, 0, 137, 24, 160, 31, 174, 68, 26, 19, 112, 112, 45, 191, 75, 205, 20, 139, 26, 117, 125, 122, 82, 112, 210, 185, 190, 73, 20, 170, 185, 214, 46, 178, 112, 181, 74, 160, 146, 195, 183, 75, 21, 4, 242, 60, 22, 9, 215, 163, 98, 218, 252, 126, 239, 41, 44, 96, 224, 255, 206, 103, 54, 168, 98, 97, 57, 165, 69, 176, 160, 141, 119, 72, 74, 161, 198, 210, 74, 79, 3, 171, 23, 74, 15, 222, 227, 242, 79, 174, 220, 97, 228, 50, 25, 34, 12, 84, 42, 139, 237, 181, 231, 160, 24, 237, 222, 18, 73, 14, 164, 210, 227, 226, 3, 2, 88, 170, 247, 77, 215, 38, 229, 131, 247, 237, 86, 22, 131, 176, 87, 114, 146, 170, 149, 188, 160, 143, 70, 241, 22, 116, 183, 217, 56, 189, 154, 60, 98, 174, 45, 76, 80, 252, 141, 249, 210, 132, 8, 99, 220, 30, 72, 171, 129, 181, 66, 25, 120, 192, 1])
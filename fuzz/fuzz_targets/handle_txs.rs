@@ -0,0 +1,7 @@
+#![no_main]
+use creak::messages::handle_txs;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = handle_txs(&rlp::Rlp::new(data));
+});
@@ -0,0 +1,10 @@
+#![no_main]
+use creak::messages::KeyBlock;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    // Whatever parses must serialize back to the same bytes.
+    if let Ok(header) = KeyBlock::from_bytes(data) {
+        assert_eq!(header.to_bytes(), data);
+    }
+});
@@ -0,0 +1,7 @@
+#![no_main]
+use creak::rlp_val::RlpVal;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = RlpVal::from_rlp(&rlp::Rlp::new(data));
+});
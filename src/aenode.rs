@@ -1,11 +1,12 @@
+use base58check::{FromBase58Check, FromBase58CheckError};
 use byteorder::{BigEndian, WriteBytesExt};
 use curl::easy::Easy;
 use regex::Regex;
 use serde_json::Value;
 use std::net::IpAddr;
+use crate::error::Error;
 
-type RlpError = Box<std::error::Error>;
-
+#[derive(Clone, Debug)]
 pub struct Aenode {
     pub pub_key: String,
     pub address: IpAddr,
@@ -13,22 +14,23 @@ pub struct Aenode {
 }
 
 impl Aenode {
-    pub fn new(aenode: &String) -> Result<Aenode, Box<std::error::Error>>
+    pub fn new(aenode: &str) -> Result<Aenode, Error>
     {
-        let re = Regex::new(r"^aenode://pp_(.+)@([0-9.]+):([0-9]+)$")?;
-        let captures = re.captures(aenode).unwrap();
+        let invalid = || Error::Invalid(format!("Invalid aenode URL {}", aenode));
+        let re = Regex::new(r"^aenode://pp_(.+)@([0-9.]+):([0-9]+)$")
+            .map_err(|e| Error::Invalid(e.to_string()))?;
+        let captures = re.captures(aenode).ok_or_else(invalid)?;
         Ok(Aenode {
-            pub_key: captures[1].parse()?,
-            address: captures[2].parse()?,
-            port: captures[3].parse()?,
+            pub_key: captures[1].to_string(),
+            address: captures[2].parse().map_err(|_| invalid())?,
+            port: captures[3].parse().map_err(|_| invalid())?,
         })
     }
 
-    pub fn prologue(&self, port: u16) -> Result<Vec<u8>, RlpError>
+    pub fn prologue(&self, port: u16) -> Result<Vec<u8>, Error>
     {
         let mut easy = Easy::new();
         let url = format!("http://{}:{}/v2/status", self.address, port);
-        println!("{}", url);
         easy.url(&url)?;
         let mut v = Vec::<u8>::new();
         {
@@ -40,12 +42,13 @@ impl Aenode {
             transfer.perform()?;
         }
         let json: Value = serde_json::from_str(&String::from_utf8(v)?)?;
-        println!("{:?}", json);
         let gen_hash = match json["genesis_key_block_hash"].as_str() {
-            Some(x) => String::from(x),
+            Some(x) => match x.strip_prefix("kh_") {
+                Some(x) => String::from(x),
+                None => bail!("Genesis hash {} is not a kh_ hash", x),
+            },
             None => bail!("Genesis block not found"),
         };
-        let gen_hash = String::from_utf8(gen_hash[3..].as_bytes().to_vec())?;
         let network_id = match json["network_id"].as_str() {
             Some(x) => String::from(x),
             None => bail!("Network id not found"),
@@ -57,19 +60,23 @@ impl Aenode {
 /*
  * decode base 58, adding the version byte onto the returned value
  */
-fn decodebase58check(data: &String) -> Vec<u8>
+pub(crate) fn decodebase58check(data: &str) -> Result<Vec<u8>, Error>
 {
-    let mut result = base58check::FromBase58Check::from_base58check(data.as_str()).unwrap().1;
-    result.insert(0, base58check::FromBase58Check::from_base58check(data.as_str()).unwrap().0);
-    result
+    let (version, mut result) = match data.from_base58check() {
+        Ok(x) => x,
+        Err(FromBase58CheckError::InvalidChecksum) => return Err(Error::BadChecksum),
+        Err(e) => bail!("Invalid base58check data: {:?}", e),
+    };
+    result.insert(0, version);
+    Ok(result)
 }
 
 
 
-pub fn prologue(version: u64, genesis_hash: &String, network_id: &String) ->
-    Result<Vec<u8>, RlpError>
+pub fn prologue(version: u64, genesis_hash: &str, network_id: &str) ->
+    Result<Vec<u8>, Error>
 {
-    let mut genesis_binary = decodebase58check(&genesis_hash);
+    let mut genesis_binary = decodebase58check(genesis_hash)?;
     let network_id_binary = network_id.as_bytes();
     let mut result = vec!();
     result.write_u64::<BigEndian>(version)?;
@@ -0,0 +1,356 @@
+use crate::aenode::Aenode;
+use crate::connection::{initiator, DEFAULT_API_PORT, HANDSHAKE_TIMEOUT, NOISE_MAX_MSG_LEN};
+use crate::error::Error;
+use crate::fragment::{self, FragmentAssembler};
+use crate::frame::MAX_FRAME_LEN;
+use crate::keypair::decode_public_key;
+use crate::messages::{Message, Ping, MSG_PING};
+use byteorder::{BigEndian, ByteOrder};
+use bytes::{Bytes, BytesMut};
+use futures::{ready, Sink, SinkExt, Stream, StreamExt};
+use snow::{Keypair, Session};
+use std::collections::VecDeque;
+use std::future::poll_fn;
+use std::io;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::net::TcpStream;
+use tokio_util::codec::{Decoder, Encoder, Framed, LengthDelimitedCodec};
+
+fn transport_error(what: &str, e: snow::SnowError) -> Error {
+    Error::Transport(io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", what, e)))
+}
+
+fn length_codec() -> LengthDelimitedCodec {
+    LengthDelimitedCodec::builder()
+        .length_field_length(2)
+        .max_frame_length(MAX_FRAME_LEN)
+        .new_codec()
+}
+
+/*
+ * The async counterpart of `PeerConnection`: the same Noise_XK handshake
+ * and the same 16-bit length framing, with fragments reassembled and
+ * split on the way through. It is a `Stream` and `Sink` of `Message`s;
+ * `recv_message` and `send_message` deal in the raw layout, the 16-bit
+ * message type followed by the RLP body. With `answer_pings`, pings are
+ * answered as the stream reads them, as `PeerConnection` does.
+ */
+pub struct AsyncPeerConnection {
+    inner: Framed<TcpStream, NoiseCodec>,
+    ping: Option<Ping>,
+    /// Answers to the peer's pings not yet handed to `inner`.
+    answers: VecDeque<Vec<u8>>,
+    /// Whether `inner` holds answers not yet flushed.
+    unflushed: bool,
+}
+
+impl AsyncPeerConnection {
+    /// Connect to `aenode`, fetching the prologue from its HTTP API on the
+    /// default port.
+    pub async fn connect(
+        aenode: &Aenode,
+        static_keypair: &Keypair,
+    ) -> Result<AsyncPeerConnection, Error> {
+        let node = aenode.clone();
+        let prologue = tokio::task::spawn_blocking(move || node.prologue(DEFAULT_API_PORT))
+            .await
+            .map_err(io::Error::from)??;
+        AsyncPeerConnection::connect_with_prologue(aenode, static_keypair, &prologue).await
+    }
+
+    /// Connect to `aenode` using an already known prologue, as built by
+    /// `aenode::prologue`.
+    pub async fn connect_with_prologue(
+        aenode: &Aenode,
+        static_keypair: &Keypair,
+        prologue: &[u8],
+    ) -> Result<AsyncPeerConnection, Error> {
+        let remote_static = decode_public_key(&aenode.pub_key)?;
+        let noise = initiator(static_keypair, &remote_static, prologue)?;
+        let stream = TcpStream::connect(SocketAddr::new(aenode.address, aenode.port)).await?;
+        let framed = Framed::new(stream, length_codec());
+        match tokio::time::timeout(HANDSHAKE_TIMEOUT, handshake(framed, noise)).await {
+            Ok(inner) => Ok(AsyncPeerConnection {
+                inner: inner?,
+                ping: None,
+                answers: VecDeque::new(),
+                unflushed: false,
+            }),
+            Err(_) => Err(Error::Transport(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "Noise handshake timed out",
+            ))),
+        }
+    }
+
+    /// The responder's static public key, as proven during the handshake.
+    pub fn remote_static(&self) -> Option<&[u8]> {
+        self.inner.codec().noise.get_remote_static()
+    }
+
+    /// Answer every ping the peer sends with `ping`, our own view of the
+    /// chain. The peer's pings are still returned by the stream.
+    pub fn answer_pings(&mut self, ping: Ping) {
+        self.ping = Some(ping);
+    }
+
+    /// The ping we answer with, to update our top and peers as they change.
+    pub fn ping_mut(&mut self) -> Option<&mut Ping> {
+        self.ping.as_mut()
+    }
+
+    /// Encrypt and send one message: 16-bit BE message type followed by
+    /// the RLP body, as `PeerConnection::send_message` does.
+    pub async fn send_message(&mut self, msg: &[u8]) -> Result<(), Error> {
+        self.inner.send(msg.to_vec()).await
+    }
+
+    /// Receive and decrypt one message, in the layout `handle_message`
+    /// expects. Returns `Ok(None)` once the peer has closed the connection.
+    pub async fn recv_message(&mut self) -> Result<Option<Vec<u8>>, Error> {
+        poll_fn(|cx| self.poll_recv_message(cx)).await.transpose()
+    }
+
+    /// Encode and send a typed message.
+    pub async fn send(&mut self, msg: &Message) -> Result<(), Error> {
+        self.send_message(&msg.encode()?).await
+    }
+
+    /// Receive the next message and decode it. Returns `Ok(None)` once the
+    /// peer has closed the connection.
+    pub async fn recv(&mut self) -> Result<Option<Message>, Error> {
+        self.next().await.transpose()
+    }
+
+    fn poll_recv_message(&mut self, cx: &mut Context) -> Poll<Option<Result<Vec<u8>, Error>>> {
+        if let Err(e) = self.poll_answers(cx) {
+            return Poll::Ready(Some(Err(e)));
+        }
+        let msg = match ready!(Pin::new(&mut self.inner).poll_next(cx)) {
+            Some(Ok(msg)) => msg,
+            other => return Poll::Ready(other),
+        };
+        if msg.len() >= 2 && BigEndian::read_u16(&msg[0..2]) == MSG_PING {
+            if let Err(e) = self.answer_ping(&msg[2..], cx) {
+                return Poll::Ready(Some(Err(e)));
+            }
+        }
+        Poll::Ready(Some(Ok(msg)))
+    }
+
+    fn answer_ping(&mut self, msg: &[u8], cx: &mut Context) -> Result<(), Error> {
+        if let Some(ref ours) = self.ping {
+            let response = ours.response_to(&Ping::from_rlp(&rlp::Rlp::new(msg))?)?;
+            self.answers.push_back(response);
+        }
+        self.poll_answers(cx)
+    }
+
+    // Send what ping answers the socket takes without waiting; the rest
+    // go on the next poll.
+    fn poll_answers(&mut self, cx: &mut Context) -> Result<(), Error> {
+        while !self.answers.is_empty() {
+            match Pin::new(&mut self.inner).poll_ready(cx) {
+                Poll::Ready(ready) => ready?,
+                Poll::Pending => return Ok(()),
+            }
+            let answer = self.answers.pop_front().unwrap_or_default();
+            Pin::new(&mut self.inner).start_send(answer)?;
+            self.unflushed = true;
+        }
+        if self.unflushed {
+            if let Poll::Ready(flushed) = Pin::new(&mut self.inner).poll_flush(cx) {
+                flushed?;
+                self.unflushed = false;
+            }
+        }
+        Ok(())
+    }
+}
+
+// XK initiator: -> e, es  <- e, ee  -> s, se
+async fn handshake(
+    mut framed: Framed<TcpStream, LengthDelimitedCodec>,
+    mut noise: Session,
+) -> Result<Framed<TcpStream, NoiseCodec>, Error> {
+    let mut buf = vec![0u8; NOISE_MAX_MSG_LEN];
+    let len = noise
+        .write_message(&[], &mut buf)
+        .map_err(|e| Error::Handshake(format!("write failed: {}", e)))?;
+    framed.send(Bytes::copy_from_slice(&buf[..len])).await?;
+    let msg = match framed.next().await {
+        Some(msg) => msg?,
+        None => return Err(Error::Handshake(String::from("connection closed"))),
+    };
+    noise
+        .read_message(&msg, &mut buf)
+        .map_err(|e| Error::Handshake(format!("read failed: {}", e)))?;
+    let len = noise
+        .write_message(&[], &mut buf)
+        .map_err(|e| Error::Handshake(format!("write failed: {}", e)))?;
+    framed.send(Bytes::copy_from_slice(&buf[..len])).await?;
+    let noise = noise
+        .into_transport_mode()
+        .map_err(|e| Error::Handshake(format!("incomplete: {}", e)))?;
+    Ok(framed.map_codec(|inner| NoiseCodec {
+        inner,
+        noise,
+        buf,
+        fragments: FragmentAssembler::new(),
+    }))
+}
+
+impl Stream for AsyncPeerConnection {
+    type Item = Result<Message, Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        let msg = ready!(self.poll_recv_message(cx));
+        Poll::Ready(msg.map(|msg| Message::from_bytes(&msg?)))
+    }
+}
+
+impl Sink<Message> for AsyncPeerConnection {
+    type Error = Error;
+
+    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Error>> {
+        Pin::new(&mut self.inner).poll_ready(cx)
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, msg: Message) -> Result<(), Error> {
+        let msg = msg.encode()?;
+        Pin::new(&mut self.inner).start_send(msg)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Error>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Error>> {
+        Pin::new(&mut self.inner).poll_close(cx)
+    }
+}
+
+/// Noise transport over length delimited frames, with fragmentation.
+pub struct NoiseCodec {
+    inner: LengthDelimitedCodec,
+    noise: Session,
+    buf: Vec<u8>,
+    fragments: FragmentAssembler,
+}
+
+impl Decoder for NoiseCodec {
+    type Item = Vec<u8>;
+    type Error = Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Vec<u8>>, Error> {
+        loop {
+            let frame = match self.inner.decode(src)? {
+                Some(frame) => frame,
+                None => return Ok(None),
+            };
+            let len = self
+                .noise
+                .read_message(&frame, &mut self.buf)
+                .map_err(|e| transport_error("Noise decryption failed", e))?;
+            let msg = self.buf[..len].to_vec();
+            if let Some(msg) = self.fragments.receive(msg)? {
+                return Ok(Some(msg));
+            }
+        }
+    }
+
+    fn decode_eof(&mut self, src: &mut BytesMut) -> Result<Option<Vec<u8>>, Error> {
+        match self.decode(src)? {
+            Some(msg) => Ok(Some(msg)),
+            None if src.is_empty() && !self.fragments.in_progress() => Ok(None),
+            None => Err(Error::Transport(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "Connection closed inside a message",
+            ))),
+        }
+    }
+}
+
+impl Encoder<Vec<u8>> for NoiseCodec {
+    type Error = Error;
+
+    fn encode(&mut self, msg: Vec<u8>, dst: &mut BytesMut) -> Result<(), Error> {
+        for frame in fragment::split(&msg)? {
+            let len = self
+                .noise
+                .write_message(&frame, &mut self.buf)
+                .map_err(|e| transport_error("Noise encryption failed", e))?;
+            self.inner.encode(Bytes::copy_from_slice(&self.buf[..len]), dst)?;
+        }
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn test_async_connect_with_prologue() {
+    use crate::messages::MSG_P2PRESPONSE;
+    use crate::PARAMS;
+    use snow::Builder;
+
+    let prologue = b"test prologue".to_vec();
+    let responder_keys = Builder::new(PARAMS.clone()).generate_keypair().unwrap();
+    let initiator_keys = Builder::new(PARAMS.clone()).generate_keypair().unwrap();
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let aenode = Aenode {
+        pub_key: crate::rlp_val::to_base58check(&responder_keys.public),
+        address: listener.local_addr().unwrap().ip(),
+        port: listener.local_addr().unwrap().port(),
+    };
+
+    let ours = Ping::new(3015, 32, vec![0x80; 32], 1, vec![9; 32], true, vec![]);
+    let theirs = Ping::new(3015, 1, vec![0x80; 32], 1, vec![9; 32], false, vec![]);
+    let responder_prologue = prologue.clone();
+    let responder = tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut framed = Framed::new(stream, length_codec());
+        let mut buf = vec![0u8; NOISE_MAX_MSG_LEN];
+        let mut noise = Builder::new(PARAMS.clone())
+            .local_private_key(&responder_keys.private)
+            .prologue(&responder_prologue)
+            .build_responder()
+            .unwrap();
+        let msg = framed.next().await.unwrap().unwrap();
+        noise.read_message(&msg, &mut buf).unwrap();
+        let len = noise.write_message(&[], &mut buf).unwrap();
+        framed.send(Bytes::copy_from_slice(&buf[..len])).await.unwrap();
+        let msg = framed.next().await.unwrap().unwrap();
+        noise.read_message(&msg, &mut buf).unwrap();
+        let noise = noise.into_transport_mode().unwrap();
+        let mut framed = framed.map_codec(|inner| NoiseCodec {
+            inner,
+            noise,
+            buf,
+            fragments: FragmentAssembler::new(),
+        });
+        let msg = framed.next().await.unwrap().unwrap();
+        framed.send(msg).await.unwrap();
+
+        framed.send(theirs.rlp().unwrap()).await.unwrap();
+        let response = framed.next().await.unwrap().unwrap();
+        assert_eq!(BigEndian::read_u16(&response[0..2]), MSG_P2PRESPONSE);
+        framed.send(Message::Close.encode().unwrap()).await.unwrap();
+    });
+
+    let mut conn = AsyncPeerConnection::connect_with_prologue(&aenode, &initiator_keys, &prologue)
+        .await
+        .unwrap();
+    let big: Vec<u8> = (0..100_000u32).map(|x| x as u8).collect();
+    conn.send_message(&big).await.unwrap();
+    assert_eq!(conn.recv_message().await.unwrap(), Some(big));
+
+    conn.answer_pings(ours);
+    match conn.next().await.unwrap().unwrap() {
+        Message::Ping(ping) => assert_eq!(ping.share(), 1),
+        msg => panic!("Expected a ping, got {:?}", msg),
+    }
+    assert_eq!(conn.recv().await.unwrap(), Some(Message::Close));
+    assert_eq!(conn.recv().await.unwrap(), None);
+    responder.await.unwrap();
+}
@@ -0,0 +1,292 @@
+use crate::error::Error;
+use crate::messages::Header;
+use crate::pow::Difficulty;
+use std::collections::HashMap;
+
+/*
+ * The headers we know of, as a tree linked by prev_hash and rooted at the
+ * first header we were given: genesis, or a block we trust to start
+ * syncing from. Every node keeps the total difficulty from genesis, and
+ * the top is the heaviest node. Micro blocks add no difficulty, so they
+ * only move the top when they build on it; between equally heavy branches
+ * the one we saw first stays on top.
+ *
+ * Only the links are checked here. Proof of work and micro block
+ * signatures are checked with `KeyBlock::verify_pow` and
+ * `MicroBlockHeader::verify_signature`.
+ */
+pub struct HeaderChain {
+    nodes: HashMap<[u8; 32], Node>,
+    top: [u8; 32],
+}
+
+struct Node {
+    header: Header,
+    /// The key block this block belongs to: itself for a key block.
+    key_hash: [u8; 32],
+    difficulty: Difficulty,
+    /// Blocks between this one and the root.
+    depth: u64,
+    children: usize,
+}
+
+/// What an insert did to the chain, beyond adding the header.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ChainEvent {
+    /// The header built on the top and is the new top.
+    NewTop { hash: [u8; 32] },
+    /// The header built on a block that already had a successor, starting
+    /// a new branch.
+    Fork { hash: [u8; 32], parent: [u8; 32] },
+    /// The top moved to another branch. `removed` are the blocks that left
+    /// the main chain, from the old top down, `added` those that joined
+    /// it, from just after `ancestor` up to the new top.
+    Reorg {
+        old_top: [u8; 32],
+        new_top: [u8; 32],
+        ancestor: [u8; 32],
+        removed: Vec<[u8; 32]>,
+        added: Vec<[u8; 32]>,
+    },
+}
+
+impl HeaderChain {
+    /// A chain starting at genesis.
+    pub fn new(genesis: Header) -> HeaderChain {
+        let difficulty = genesis.difficulty();
+        HeaderChain::with_difficulty(genesis, difficulty)
+    }
+
+    /// A chain starting at some later block, whose total difficulty, its
+    /// own included, we take on trust.
+    pub fn with_difficulty(root: Header, difficulty: Difficulty) -> HeaderChain {
+        let hash = root.hash();
+        let key_hash = match root {
+            Header::Key(_) => hash,
+            Header::Micro(_) => *root.prev_key_hash(),
+        };
+        let mut nodes = HashMap::new();
+        nodes.insert(hash, Node { header: root, key_hash, difficulty, depth: 0, children: 0 });
+        HeaderChain { nodes, top: hash }
+    }
+
+    /// Add a header whose previous block we already have. A key block must
+    /// be one higher than that block, a micro block at the same height,
+    /// and both must name the previous block's key block in
+    /// prev_key_hash. Inserting a header twice does nothing.
+    pub fn insert(&mut self, header: Header) -> Result<Vec<ChainEvent>, Error> {
+        let hash = header.hash();
+        if self.nodes.contains_key(&hash) {
+            return Ok(vec![]);
+        }
+        let (prev_hash, prev_key_hash) = (*header.prev_hash(), *header.prev_key_hash());
+        let parent = match self.nodes.get(&prev_hash) {
+            Some(parent) => parent,
+            None => bail!("Previous block {} is unknown", hex::encode(prev_hash)),
+        };
+        let parent_height = parent.header.height();
+        let expected = match header {
+            Header::Key(_) => parent_height + 1,
+            Header::Micro(_) => parent_height,
+        };
+        if header.height() != expected {
+            bail!("Block at height {} follows one at height {}", header.height(), parent_height);
+        }
+        if prev_key_hash != parent.key_hash {
+            bail!("Previous key block {} doesn't match the previous block's {}",
+                  hex::encode(prev_key_hash), hex::encode(parent.key_hash));
+        }
+
+        let node = Node {
+            key_hash: match header {
+                Header::Key(_) => hash,
+                Header::Micro(_) => prev_key_hash,
+            },
+            difficulty: parent.difficulty + header.difficulty(),
+            depth: parent.depth + 1,
+            children: 0,
+            header,
+        };
+        let mut events = vec![];
+        if parent.children > 0 {
+            events.push(ChainEvent::Fork { hash, parent: prev_hash });
+        }
+        let old_top = self.top;
+        let becomes_top = if prev_hash == old_top {
+            true
+        } else {
+            node.difficulty > self.nodes[&old_top].difficulty
+        };
+        if let Some(parent) = self.nodes.get_mut(&prev_hash) {
+            parent.children += 1;
+        }
+        self.nodes.insert(hash, node);
+
+        if becomes_top {
+            self.top = hash;
+            if prev_hash == old_top {
+                events.push(ChainEvent::NewTop { hash });
+            } else {
+                let (ancestor, removed, added) = self.branches(old_top, hash);
+                events.push(ChainEvent::Reorg { old_top, new_top: hash, ancestor, removed, added });
+            }
+        }
+        Ok(events)
+    }
+
+    pub fn top(&self) -> &Header {
+        &self.nodes[&self.top].header
+    }
+
+    pub fn top_hash(&self) -> &[u8; 32] {
+        &self.top
+    }
+
+    /// Total difficulty of the top, as advertised in Ping.
+    pub fn difficulty(&self) -> Difficulty {
+        self.nodes[&self.top].difficulty
+    }
+
+    pub fn get(&self, hash: &[u8; 32]) -> Option<&Header> {
+        self.nodes.get(hash).map(|node| &node.header)
+    }
+
+    pub fn contains(&self, hash: &[u8; 32]) -> bool {
+        self.nodes.contains_key(hash)
+    }
+
+    /// Total difficulty of the chain up to and including `hash`.
+    pub fn difficulty_of(&self, hash: &[u8; 32]) -> Option<Difficulty> {
+        self.nodes.get(hash).map(|node| node.difficulty)
+    }
+
+    /// True if the block is an ancestor of the top, or the top itself.
+    pub fn is_main_chain(&self, hash: &[u8; 32]) -> bool {
+        let depth = match self.nodes.get(hash) {
+            Some(node) => node.depth,
+            None => return false,
+        };
+        self.ancestors(self.top)
+            .find(|(_, node)| node.depth == depth)
+            .is_some_and(|(at, _)| &at == hash)
+    }
+
+    /// Number of blocks, on all branches.
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    // The block and its ancestors, back to the root.
+    fn ancestors(&self, hash: [u8; 32]) -> impl Iterator<Item = ([u8; 32], &Node)> {
+        let mut next = Some(hash);
+        std::iter::from_fn(move || {
+            let hash = next?;
+            let node = self.nodes.get(&hash)?;
+            next = if node.depth > 0 { Some(*node.header.prev_hash()) } else { None };
+            Some((hash, node))
+        })
+    }
+
+    // The common ancestor of two blocks, and the blocks between it and
+    // each of them: from `from` down, and up to `to`.
+    fn branches(&self, from: [u8; 32], to: [u8; 32]) -> ([u8; 32], Vec<[u8; 32]>, Vec<[u8; 32]>) {
+        let mut removed = vec![];
+        let mut added = vec![];
+        let (mut a, mut b) = (from, to);
+        while a != b {
+            let (depth_a, depth_b) = (self.nodes[&a].depth, self.nodes[&b].depth);
+            if depth_a >= depth_b {
+                removed.push(a);
+                a = *self.nodes[&a].header.prev_hash();
+            }
+            if depth_b >= depth_a {
+                added.push(b);
+                b = *self.nodes[&b].header.prev_hash();
+            }
+        }
+        added.reverse();
+        (a, removed, added)
+    }
+}
+
+#[test]
+fn test_header_chain() {
+    use crate::messages::{KeyBlock, MicroBlockHeader};
+
+    // Target 0x2100ffff has difficulty 2^24, 0x2000ffff 2^32.
+    let key = |prev: &Header, prev_key: &Header, target: u32| {
+        Header::Key(KeyBlock::new(1, prev.height() + 1, prev.hash(), prev_key.hash(), [0; 32],
+                                  [1; 32], [2; 32], target, [0; 168], 0, 0))
+    };
+    let micro = |prev: &Header, prev_key: &Header, txs_hash: [u8; 32]| {
+        Header::Micro(MicroBlockHeader::new(1, prev.height(), prev.hash(), prev_key.hash(),
+                                            [0; 32], txs_hash, 0, None, [0; 64]))
+    };
+    let genesis = Header::Key(KeyBlock::new(1, 0, [0; 32], [0; 32], [0; 32], [0; 32], [0; 32],
+                                            0x2100_ffff, [0; 168], 0, 0));
+    let mut chain = HeaderChain::new(genesis.clone());
+    assert_eq!(chain.difficulty(), Difficulty(1 << 24));
+
+    let k1 = key(&genesis, &genesis, 0x2100_ffff);
+    let m1 = micro(&k1, &k1, [1; 32]);
+    let k2 = key(&m1, &k1, 0x2100_ffff);
+    for header in &[&k1, &m1, &k2] {
+        assert_eq!(chain.insert((*header).clone()).unwrap(),
+                   vec![ChainEvent::NewTop { hash: header.hash() }]);
+    }
+    assert_eq!(chain.insert(k2.clone()).unwrap(), vec![]);
+    assert_eq!(chain.top(), &k2);
+    assert_eq!(chain.difficulty(), Difficulty(3 << 24));
+    assert_eq!(chain.difficulty_of(&m1.hash()), Some(Difficulty(2 << 24)));
+
+    // A second micro block on k1, and a key block on that as heavy as k2:
+    // the branch we saw first stays on top.
+    let m1b = micro(&k1, &k1, [2; 32]);
+    assert_eq!(chain.insert(m1b.clone()).unwrap(),
+               vec![ChainEvent::Fork { hash: m1b.hash(), parent: k1.hash() }]);
+    let k2b = key(&m1b, &k1, 0x2100_ffff);
+    assert_eq!(chain.insert(k2b.clone()).unwrap(), vec![]);
+    assert_eq!(chain.top_hash(), &k2.hash());
+    assert!(chain.is_main_chain(&m1.hash()));
+    assert!(!chain.is_main_chain(&m1b.hash()));
+
+    let k3b = key(&k2b, &k2b, 0x2100_ffff);
+    assert_eq!(chain.insert(k3b.clone()).unwrap(), vec![ChainEvent::Reorg {
+        old_top: k2.hash(),
+        new_top: k3b.hash(),
+        ancestor: k1.hash(),
+        removed: vec![k2.hash(), m1.hash()],
+        added: vec![m1b.hash(), k2b.hash(), k3b.hash()],
+    }]);
+    assert!(chain.is_main_chain(&genesis.hash()));
+    assert!(chain.is_main_chain(&m1b.hash()));
+    assert!(!chain.is_main_chain(&m1.hash()));
+
+    // One heavier key block outweighs two.
+    let k2c = key(&k1, &k1, 0x2000_ffff);
+    assert_eq!(chain.insert(k2c.clone()).unwrap(), vec![
+        ChainEvent::Fork { hash: k2c.hash(), parent: k1.hash() },
+        ChainEvent::Reorg {
+            old_top: k3b.hash(),
+            new_top: k2c.hash(),
+            ancestor: k1.hash(),
+            removed: vec![k3b.hash(), k2b.hash(), m1b.hash()],
+            added: vec![k2c.hash()],
+        },
+    ]);
+    assert_eq!(chain.difficulty(), Difficulty((2 << 24) + (1 << 32)));
+    assert_eq!(chain.len(), 8);
+
+    let orphan = key(&key(&k2c, &k2c, 0x2100_ffff), &k2c, 0x2100_ffff);
+    assert!(chain.insert(orphan).is_err());
+    let wrong_height = Header::Micro(MicroBlockHeader::new(
+        1, k2c.height() + 1, k2c.hash(), k2c.hash(), [0; 32], [3; 32], 0, None, [0; 64]));
+    assert!(chain.insert(wrong_height).is_err());
+    assert!(chain.insert(micro(&k2c, &k1, [3; 32])).is_err());
+    assert!(chain.insert(key(&m1, &m1, 0x2100_ffff)).is_err());
+    assert_eq!(chain.len(), 8);
+}
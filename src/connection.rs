@@ -0,0 +1,320 @@
+use crate::aenode::Aenode;
+use crate::error::Error;
+use crate::fragment::{self, FragmentAssembler};
+use crate::frame::FramedStream;
+use crate::keypair::decode_public_key;
+use crate::messages::{Message, Ping, MSG_PING};
+use byteorder::{BigEndian, ByteOrder};
+use crate::PARAMS;
+use snow::{Builder, Keypair, Session};
+use std::io;
+use std::net::{SocketAddr, TcpStream};
+use std::time::Duration;
+
+/// Largest message the noise protocol will carry, including the 16 byte tag.
+pub(crate) const NOISE_MAX_MSG_LEN: usize = 65535;
+
+/// Port of the node's HTTP API, used to fetch the genesis hash and network id.
+pub const DEFAULT_API_PORT: u16 = 3013;
+
+/// The node gives up on a handshake after five seconds, so do we.
+pub(crate) const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/*
+ * An encrypted connection to an æternity node. The sync protocol runs
+ * Noise_XK: the initiator knows the responder's static key up front (the
+ * pp_ part of the aenode:// URL) and reveals its own in the final
+ * handshake message. Every noise message, handshake or transport, is
+ * framed with a 16-bit BE length.
+ */
+pub struct PeerConnection {
+    stream: FramedStream<TcpStream>,
+    noise: Session,
+    buf: Vec<u8>,
+    fragments: FragmentAssembler,
+    ping: Option<Ping>,
+}
+
+impl PeerConnection {
+    /// Connect to `aenode`, fetching the prologue from its HTTP API on the
+    /// default port.
+    pub fn connect(aenode: &Aenode, static_keypair: &Keypair) -> Result<PeerConnection, Error> {
+        let prologue = aenode.prologue(DEFAULT_API_PORT)?;
+        PeerConnection::connect_with_prologue(aenode, static_keypair, &prologue)
+    }
+
+    /// Connect to `aenode` using an already known prologue, as built by
+    /// `aenode::prologue`.
+    pub fn connect_with_prologue(
+        aenode: &Aenode,
+        static_keypair: &Keypair,
+        prologue: &[u8],
+    ) -> Result<PeerConnection, Error> {
+        let remote_static = decode_public_key(&aenode.pub_key)?;
+        let stream = TcpStream::connect(SocketAddr::new(aenode.address, aenode.port))?;
+        let mut stream = FramedStream::new(stream);
+        let mut buf = vec![0u8; NOISE_MAX_MSG_LEN];
+        let mut noise = initiator(static_keypair, &remote_static, prologue)?;
+
+        // XK initiator: -> e, es  <- e, ee  -> s, se
+        stream.get_ref().set_read_timeout(Some(HANDSHAKE_TIMEOUT))?;
+        write_handshake(&mut stream, &mut noise, &mut buf)?;
+        read_handshake(&mut stream, &mut noise, &mut buf)?;
+        write_handshake(&mut stream, &mut noise, &mut buf)?;
+        stream.get_ref().set_read_timeout(None)?;
+
+        let noise = noise
+            .into_transport_mode()
+            .map_err(|e| Error::Handshake(format!("incomplete: {}", e)))?;
+        Ok(PeerConnection {
+            stream,
+            noise,
+            buf,
+            fragments: FragmentAssembler::new(),
+            ping: None,
+        })
+    }
+
+    /// Complete the XK responder side of the handshake on a connection a
+    /// peer opened to us. The peer must use the same prologue, i.e. be on
+    /// the same chain and network, or the handshake fails.
+    pub fn accept(
+        stream: TcpStream,
+        static_keypair: &Keypair,
+        prologue: &[u8],
+    ) -> Result<PeerConnection, Error> {
+        let mut stream = FramedStream::new(stream);
+        let mut buf = vec![0u8; NOISE_MAX_MSG_LEN];
+        let mut noise = Builder::new(PARAMS.clone())
+            .local_private_key(&static_keypair.private)
+            .prologue(prologue)
+            .build_responder()
+            .map_err(|e| Error::Handshake(format!("setup failed: {}", e)))?;
+
+        // XK responder: <- e, es  -> e, ee  <- s, se
+        stream.get_ref().set_read_timeout(Some(HANDSHAKE_TIMEOUT))?;
+        // The prologue is mixed into the first message's authentication
+        // tag, so a peer from another chain or network fails right here.
+        let msg = match stream.read_frame()? {
+            Some(msg) => msg,
+            None => return Err(Error::Handshake(String::from("connection closed"))),
+        };
+        if noise.read_message(&msg, &mut buf).is_err() {
+            return Err(Error::Handshake(String::from(
+                "peer's prologue (genesis hash and network id) or our static key does not match",
+            )));
+        }
+        write_handshake(&mut stream, &mut noise, &mut buf)?;
+        read_handshake(&mut stream, &mut noise, &mut buf)?;
+        stream.get_ref().set_read_timeout(None)?;
+
+        let noise = noise
+            .into_transport_mode()
+            .map_err(|e| Error::Handshake(format!("incomplete: {}", e)))?;
+        Ok(PeerConnection {
+            stream,
+            noise,
+            buf,
+            fragments: FragmentAssembler::new(),
+            ping: None,
+        })
+    }
+
+    /// The peer's static public key, as proven during the handshake.
+    pub fn remote_static(&self) -> Option<&[u8]> {
+        self.noise.get_remote_static()
+    }
+
+    /// Answer every ping the peer sends with `ping`, our own view of the
+    /// chain. The peer's pings are still returned by `recv_message`.
+    pub fn answer_pings(&mut self, ping: Ping) {
+        self.ping = Some(ping);
+    }
+
+    /// The ping we answer with, to update our top and peers as they change.
+    pub fn ping_mut(&mut self) -> Option<&mut Ping> {
+        self.ping.as_mut()
+    }
+
+    /// Make `recv_message` give up with a `WouldBlock` or `TimedOut`
+    /// transport error if nothing arrives for `timeout`. A message cut
+    /// short by the timeout is picked up where it was left by the next
+    /// call.
+    pub fn set_read_timeout(&self, timeout: Option<Duration>) -> Result<(), Error> {
+        Ok(self.stream.get_ref().set_read_timeout(timeout)?)
+    }
+
+    /// The timeout `set_read_timeout` last set; `None` waits forever.
+    pub fn read_timeout(&self) -> Result<Option<Duration>, Error> {
+        Ok(self.stream.get_ref().read_timeout()?)
+    }
+
+    /// Encrypt and send one message: 16-bit BE message type followed by
+    /// the RLP body, as produced by e.g. `Ping::rlp`. Messages too big for
+    /// a single noise message are sent as MSG_FRAGMENT messages.
+    pub fn send_message(&mut self, msg: &[u8]) -> Result<(), Error> {
+        for frame in fragment::split(msg)? {
+            self.send_frame(&frame)?;
+        }
+        Ok(())
+    }
+
+    fn send_frame(&mut self, msg: &[u8]) -> Result<(), Error> {
+        let len = self
+            .noise
+            .write_message(msg, &mut self.buf)
+            .map_err(|e| transport_error("Noise encryption failed", e))?;
+        self.stream.write_frame(&self.buf[..len])?;
+        Ok(())
+    }
+
+    /// Receive and decrypt one message, in the layout `handle_message`
+    /// expects. Fragmented messages are reassembled before being returned.
+    /// Returns `Ok(None)` once the peer has closed the connection.
+    pub fn recv_message(&mut self) -> Result<Option<Vec<u8>>, Error> {
+        loop {
+            let msg = match self.recv_frame()? {
+                Some(msg) => msg,
+                None if self.fragments.in_progress() => {
+                    return Err(Error::Transport(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "Connection closed inside a fragmented message",
+                    )))
+                }
+                None => return Ok(None),
+            };
+            if let Some(msg) = self.fragments.receive(msg)? {
+                if msg.len() >= 2 && BigEndian::read_u16(&msg[0..2]) == MSG_PING {
+                    self.answer_ping(&msg[2..])?;
+                }
+                return Ok(Some(msg));
+            }
+        }
+    }
+
+    /// Encode and send a typed message.
+    pub fn send(&mut self, msg: &Message) -> Result<(), Error> {
+        self.send_message(&msg.encode()?)
+    }
+
+    /// Receive the next message and decode it. Returns `Ok(None)` once the
+    /// peer has closed the connection.
+    pub fn recv(&mut self) -> Result<Option<Message>, Error> {
+        match self.recv_message()? {
+            Some(msg) => Ok(Some(Message::from_bytes(&msg)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn answer_ping(&mut self, msg: &[u8]) -> Result<(), Error> {
+        let response = match self.ping {
+            Some(ref ours) => ours.response_to(&Ping::from_rlp(&rlp::Rlp::new(msg))?)?,
+            None => return Ok(()),
+        };
+        self.send_message(&response)
+    }
+
+    fn recv_frame(&mut self) -> Result<Option<Vec<u8>>, Error> {
+        let msg = match self.stream.read_frame()? {
+            Some(msg) => msg,
+            None => return Ok(None),
+        };
+        let len = self
+            .noise
+            .read_message(&msg, &mut self.buf)
+            .map_err(|e| transport_error("Noise decryption failed", e))?;
+        Ok(Some(self.buf[..len].to_vec()))
+    }
+}
+
+fn transport_error(what: &str, e: snow::SnowError) -> Error {
+    Error::Transport(io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", what, e)))
+}
+
+/// Noise state for the initiator side of the XK handshake.
+pub(crate) fn initiator(
+    static_keypair: &Keypair,
+    remote_static: &[u8],
+    prologue: &[u8],
+) -> Result<Session, Error> {
+    let noise = Builder::new(PARAMS.clone())
+        .local_private_key(&static_keypair.private)
+        .remote_public_key(remote_static)
+        .prologue(prologue)
+        .build_initiator()
+        .map_err(|e| Error::Handshake(format!("setup failed: {}", e)))?;
+    Ok(noise)
+}
+
+fn write_handshake(
+    stream: &mut FramedStream<TcpStream>,
+    noise: &mut Session,
+    buf: &mut [u8],
+) -> Result<(), Error> {
+    let len = noise
+        .write_message(&[], buf)
+        .map_err(|e| Error::Handshake(e.to_string()))?;
+    stream.write_frame(&buf[..len])?;
+    Ok(())
+}
+
+fn read_handshake(
+    stream: &mut FramedStream<TcpStream>,
+    noise: &mut Session,
+    buf: &mut [u8],
+) -> Result<(), Error> {
+    let msg = match stream.read_frame()? {
+        Some(msg) => msg,
+        None => return Err(Error::Handshake(String::from("connection closed"))),
+    };
+    noise
+        .read_message(&msg, buf)
+        .map_err(|e| Error::Handshake(e.to_string()))?;
+    Ok(())
+}
+
+#[test]
+fn test_connect_with_prologue() {
+    use std::net::TcpListener;
+
+    let prologue = b"test prologue".to_vec();
+    let responder_keys = Builder::new(PARAMS.clone()).generate_keypair().unwrap();
+    let initiator_keys = Builder::new(PARAMS.clone()).generate_keypair().unwrap();
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let aenode = Aenode {
+        pub_key: crate::rlp_val::to_base58check(&responder_keys.public),
+        address: listener.local_addr().unwrap().ip(),
+        port: listener.local_addr().unwrap().port(),
+    };
+
+    let responder_prologue = prologue.clone();
+    let responder_public = responder_keys.public.clone();
+    let responder = std::thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut stream = FramedStream::new(stream);
+        let mut buf = vec![0u8; NOISE_MAX_MSG_LEN];
+        let mut noise = Builder::new(PARAMS.clone())
+            .local_private_key(&responder_keys.private)
+            .prologue(&responder_prologue)
+            .build_responder()
+            .unwrap();
+        read_handshake(&mut stream, &mut noise, &mut buf).unwrap();
+        write_handshake(&mut stream, &mut noise, &mut buf).unwrap();
+        read_handshake(&mut stream, &mut noise, &mut buf).unwrap();
+        let remote = noise.get_remote_static().unwrap().to_vec();
+        let mut noise = noise.into_transport_mode().unwrap();
+        let msg = stream.read_frame().unwrap().unwrap();
+        let len = noise.read_message(&msg, &mut buf).unwrap();
+        let reply = buf[..len].to_vec();
+        let len = noise.write_message(&reply, &mut buf).unwrap();
+        stream.write_frame(&buf[..len]).unwrap();
+        remote
+    });
+
+    let mut conn =
+        PeerConnection::connect_with_prologue(&aenode, &initiator_keys, &prologue).unwrap();
+    assert_eq!(conn.remote_static().unwrap(), &responder_public[..]);
+    conn.send_message(&[0, 1, 192]).unwrap();
+    assert_eq!(conn.recv_message().unwrap(), Some(vec![0, 1, 192]));
+    assert_eq!(responder.join().unwrap(), initiator_keys.public);
+}
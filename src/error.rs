@@ -0,0 +1,120 @@
+use simple_error::SimpleError;
+use std::fmt;
+use std::io;
+
+/*
+ * Everything that can go wrong in creak. Whatever a peer sends us, the
+ * decoders report bad input with one of these rather than panicking, so a
+ * single malformed message only costs that message.
+ */
+#[derive(Debug)]
+pub enum Error {
+    /// `what` is `len` bytes long, but needs at least `needed`.
+    Truncated {
+        what: &'static str,
+        len: usize,
+        needed: usize,
+    },
+    /// A tag or type field holds a value we don't know; `what` names the
+    /// field, e.g. "message type".
+    UnknownTag { what: &'static str, tag: u64 },
+    UnsupportedVersion { what: &'static str, version: u64 },
+    /// A base58check string whose checksum doesn't match its payload.
+    BadChecksum,
+    /// Input that isn't well formed RLP.
+    Rlp(rlp::DecoderError),
+    /// Input that is well formed but makes no sense, described.
+    Invalid(String),
+    /// The connection, file or HTTP request failed, or timed out.
+    Transport(io::Error),
+    /// The noise handshake failed: wrong key, wrong prologue, or a peer
+    /// that went away in the middle of it.
+    Handshake(String),
+    /// The peer answered a request with a failure, for this reason.
+    Peer(String),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Truncated { what, len, needed } => {
+                write!(f, "{} of {} bytes is too short, needs {}", what, len, needed)
+            }
+            Error::UnknownTag { what, tag } => write!(f, "Unknown {} {}", what, tag),
+            Error::UnsupportedVersion { what, version } => {
+                write!(f, "Unsupported {} version {}", what, version)
+            }
+            Error::BadChecksum => write!(f, "Bad base58check checksum"),
+            Error::Rlp(e) => write!(f, "Invalid RLP: {}", e),
+            Error::Invalid(reason) => write!(f, "{}", reason),
+            Error::Transport(e) => write!(f, "{}", e),
+            Error::Handshake(reason) => write!(f, "Noise handshake failed: {}", reason),
+            Error::Peer(reason) => write!(f, "Peer failed the request: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Rlp(e) => Some(e),
+            Error::Transport(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Error {
+        Error::Transport(e)
+    }
+}
+
+impl From<curl::Error> for Error {
+    fn from(e: curl::Error) -> Error {
+        Error::Transport(io::Error::other(e))
+    }
+}
+
+impl From<rlp::DecoderError> for Error {
+    fn from(e: rlp::DecoderError) -> Error {
+        Error::Rlp(e)
+    }
+}
+
+// What `bail!` produces, given a message or a format string.
+impl From<&str> for Error {
+    fn from(reason: &str) -> Error {
+        Error::Invalid(reason.to_string())
+    }
+}
+
+impl From<SimpleError> for Error {
+    fn from(e: SimpleError) -> Error {
+        Error::Invalid(e.as_str().to_string())
+    }
+}
+
+impl From<std::string::FromUtf8Error> for Error {
+    fn from(e: std::string::FromUtf8Error) -> Error {
+        Error::Invalid(format!("Invalid UTF-8: {}", e))
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Error {
+        Error::Invalid(format!("Invalid JSON: {}", e))
+    }
+}
+
+#[test]
+fn test_error_display() {
+    let e = Error::Truncated { what: "Key block", len: 12, needed: 364 };
+    assert_eq!(e.to_string(), "Key block of 12 bytes is too short, needs 364");
+    let e: Error = SimpleError::new("Peer key must be 32 bytes").into();
+    assert_eq!(e.to_string(), "Peer key must be 32 bytes");
+    let e: Error = io::Error::new(io::ErrorKind::TimedOut, "timed out").into();
+    assert!(std::error::Error::source(&e).is_some());
+}
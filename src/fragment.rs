@@ -0,0 +1,196 @@
+use crate::error::Error;
+use crate::messages::MSG_FRAGMENT;
+use byteorder::{BigEndian, ByteOrder, WriteBytesExt};
+
+/// Largest plaintext that fits in one noise message: 65535 bytes less the
+/// 16 byte authentication tag.
+pub const MAX_PACKET_SIZE: usize = 65535 - 16;
+
+/// Message type, index and count take 6 bytes of each fragment.
+const FRAGMENT_DATA_SIZE: usize = MAX_PACKET_SIZE - 6;
+
+/// Refuse to reassemble messages bigger than this many bytes.
+pub const DEFAULT_MAX_MESSAGE_SIZE: usize = 16 * 1024 * 1024;
+
+/// Refuse to reassemble messages split into more than this many fragments.
+pub const DEFAULT_MAX_FRAGMENTS: u16 = 512;
+
+/*
+Messages too big for a single noise frame are split by the node into
+MSG_FRAGMENT messages. These are not RLP encoded, fields:
+
+Index :: u16 - 1-based index of this fragment
+Count :: u16 - total number of fragments
+Data :: bytes - the next chunk of the original message
+
+Fragments are sent back to back, in order, and the concatenated chunks
+are the original message including its 16-bit message type.
+*/
+pub struct FragmentAssembler {
+    max_size: usize,
+    max_fragments: u16,
+    count: u16,
+    received: u16,
+    buf: Vec<u8>,
+}
+
+impl FragmentAssembler {
+    pub fn new() -> FragmentAssembler {
+        FragmentAssembler::with_limits(DEFAULT_MAX_MESSAGE_SIZE, DEFAULT_MAX_FRAGMENTS)
+    }
+
+    pub fn with_limits(max_size: usize, max_fragments: u16) -> FragmentAssembler {
+        FragmentAssembler {
+            max_size,
+            max_fragments,
+            count: 0,
+            received: 0,
+            buf: vec![],
+        }
+    }
+
+    /// Add the body of a MSG_FRAGMENT message (everything after the message
+    /// type). Returns the original message once the last fragment arrives.
+    /// Any error discards the partial message.
+    pub fn push(&mut self, fragment: &[u8]) -> Result<Option<Vec<u8>>, Error> {
+        match self.add(fragment) {
+            Ok(true) => {
+                self.count = 0;
+                self.received = 0;
+                Ok(Some(std::mem::take(&mut self.buf)))
+            }
+            Ok(false) => Ok(None),
+            Err(e) => {
+                self.reset();
+                Err(e)
+            }
+        }
+    }
+
+    /// Feed every incoming message (16-bit message type followed by its
+    /// body) through here. Ordinary messages are returned as they are,
+    /// fragments are collected until the original message is complete.
+    pub fn receive(&mut self, msg: Vec<u8>) -> Result<Option<Vec<u8>>, Error> {
+        if msg.len() >= 2 && BigEndian::read_u16(&msg[0..2]) == MSG_FRAGMENT {
+            return self.push(&msg[2..]);
+        }
+        if self.in_progress() {
+            self.reset();
+            bail!("Fragmented message interrupted by a message of another type");
+        }
+        Ok(Some(msg))
+    }
+
+    /// True if some, but not all, fragments of a message have arrived.
+    pub fn in_progress(&self) -> bool {
+        self.received > 0
+    }
+
+    pub fn reset(&mut self) {
+        self.count = 0;
+        self.received = 0;
+        self.buf = vec![];
+    }
+
+    fn add(&mut self, fragment: &[u8]) -> Result<bool, Error> {
+        if fragment.len() < 4 {
+            return Err(Error::Truncated { what: "Fragment", len: fragment.len(), needed: 4 });
+        }
+        let index = BigEndian::read_u16(&fragment[0..2]);
+        let count = BigEndian::read_u16(&fragment[2..4]);
+        let data = &fragment[4..];
+        if count == 0 || index == 0 || index > count {
+            bail!("Invalid fragment {} of {}", index, count);
+        }
+        if count > self.max_fragments {
+            bail!("Message split into {} fragments, limit is {}", count, self.max_fragments);
+        }
+        if index == 1 {
+            if self.in_progress() {
+                bail!("New message started after fragment {} of {}", self.received, self.count);
+            }
+            self.count = count;
+        } else if index != self.received + 1 || count != self.count {
+            bail!("Unexpected fragment {} of {} after {} of {}",
+                  index, count, self.received, self.count);
+        }
+        if self.buf.len() + data.len() > self.max_size {
+            bail!("Fragmented message exceeds {} bytes", self.max_size);
+        }
+        self.buf.extend_from_slice(data);
+        self.received = index;
+        Ok(index == count)
+    }
+}
+
+impl Default for FragmentAssembler {
+    fn default() -> FragmentAssembler {
+        FragmentAssembler::new()
+    }
+}
+
+/// Split a message (16-bit message type followed by its body) into
+/// MSG_FRAGMENT messages if it does not fit in a single noise message.
+/// Messages that fit are returned unchanged.
+pub fn split(msg: &[u8]) -> Result<Vec<Vec<u8>>, Error> {
+    if msg.len() <= MAX_PACKET_SIZE {
+        return Ok(vec![msg.to_vec()]);
+    }
+    let count = msg.len().div_ceil(FRAGMENT_DATA_SIZE);
+    if count > u16::MAX as usize {
+        bail!("Message of {} bytes is too big to send, even in {} fragments",
+              msg.len(), u16::MAX);
+    }
+    let mut fragments = vec![];
+    for (i, chunk) in msg.chunks(FRAGMENT_DATA_SIZE).enumerate() {
+        let mut fragment = Vec::with_capacity(chunk.len() + 6);
+        fragment.write_u16::<BigEndian>(MSG_FRAGMENT)?;
+        fragment.write_u16::<BigEndian>(i as u16 + 1)?;
+        fragment.write_u16::<BigEndian>(count as u16)?;
+        fragment.extend_from_slice(chunk);
+        fragments.push(fragment);
+    }
+    Ok(fragments)
+}
+
+#[test]
+fn test_split_and_reassemble() {
+    let small = vec![0, 9, 192];
+    assert_eq!(split(&small).unwrap(), vec![small.clone()]);
+
+    let big: Vec<u8> = (0..3 * MAX_PACKET_SIZE).map(|x| x as u8).collect();
+    let fragments = split(&big).unwrap();
+    assert_eq!(fragments.len(), 4);
+    let mut assembler = FragmentAssembler::new();
+    let mut result = None;
+    for fragment in fragments {
+        assert!(fragment.len() <= MAX_PACKET_SIZE);
+        assert_eq!(BigEndian::read_u16(&fragment[0..2]), MSG_FRAGMENT);
+        result = assembler.push(&fragment[2..]).unwrap();
+    }
+    assert_eq!(result, Some(big));
+}
+
+#[test]
+fn test_reassemble_fragments() {
+    let mut assembler = FragmentAssembler::new();
+    assert_eq!(assembler.push(&[0, 1, 0, 3, 0, 1]).unwrap(), None);
+    assert!(assembler.in_progress());
+    assert_eq!(assembler.push(&[0, 2, 0, 3, 192]).unwrap(), None);
+    assert_eq!(assembler.push(&[0, 3, 0, 3]).unwrap(), Some(vec![0, 1, 192]));
+    assert!(!assembler.in_progress());
+    assert_eq!(assembler.push(&[0, 1, 0, 1, 7]).unwrap(), Some(vec![7]));
+}
+
+#[test]
+fn test_reassemble_rejects_bad_fragments() {
+    let mut assembler = FragmentAssembler::with_limits(4, 3);
+    assert!(assembler.push(&[0, 1]).is_err());
+    assert!(assembler.push(&[0, 2, 0, 2, 1]).is_err());
+    assert!(assembler.push(&[0, 1, 0, 4, 1]).is_err());
+    assert!(assembler.push(&[0, 1, 0, 2, 1, 2, 3]).unwrap().is_none());
+    assert!(assembler.push(&[0, 2, 0, 2, 4, 5]).is_err());
+    assert!(!assembler.in_progress());
+    assert!(assembler.push(&[0, 1, 0, 2, 1]).unwrap().is_none());
+    assert!(assembler.push(&[0, 1, 0, 2, 1]).is_err());
+}
@@ -0,0 +1,187 @@
+use byteorder::{BigEndian, ByteOrder};
+use std::io::{self, Read, Write};
+
+/// Frames carry a 16-bit length, so they can't be longer than this.
+pub const MAX_FRAME_LEN: usize = 0xffff;
+
+/*
+ * Stream transport used under noise: 16-bit BE size followed by payload.
+ *
+ * Reads are exact, so a frame split over several TCP segments is read
+ * whole. The end of the stream between two frames is reported as Ok(None);
+ * the end of the stream inside a frame, or a length over the configured
+ * maximum, is an error. A read that fails part way through a frame, e.g.
+ * on a read timeout, keeps what it has, and the next call carries on.
+ */
+pub struct FramedStream<S> {
+    inner: S,
+    max_frame_len: usize,
+    partial: Vec<u8>,
+}
+
+impl<S: Read + Write> FramedStream<S> {
+    pub fn new(inner: S) -> FramedStream<S> {
+        FramedStream::with_max_frame_len(inner, MAX_FRAME_LEN)
+    }
+
+    pub fn with_max_frame_len(inner: S, max_frame_len: usize) -> FramedStream<S> {
+        FramedStream {
+            inner,
+            max_frame_len: std::cmp::min(max_frame_len, MAX_FRAME_LEN),
+            partial: vec![],
+        }
+    }
+
+    pub fn max_frame_len(&self) -> usize {
+        self.max_frame_len
+    }
+
+    pub fn get_ref(&self) -> &S {
+        &self.inner
+    }
+
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+
+    /// Read one frame. Returns `Ok(None)` if the stream ended cleanly
+    /// before the first byte of a frame.
+    pub fn read_frame(&mut self) -> io::Result<Option<Vec<u8>>> {
+        let mut buf = [0u8; 4096];
+        loop {
+            let needed = if self.partial.len() < 2 {
+                2
+            } else {
+                let len = BigEndian::read_u16(&self.partial[0..2]) as usize;
+                if len > self.max_frame_len {
+                    self.partial.clear();
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("frame of {} bytes exceeds maximum of {}", len, self.max_frame_len),
+                    ));
+                }
+                if self.partial.len() == len + 2 {
+                    let frame = self.partial.split_off(2);
+                    self.partial.clear();
+                    return Ok(Some(frame));
+                }
+                len + 2
+            };
+            let want = std::cmp::min(needed - self.partial.len(), buf.len());
+            match self.inner.read(&mut buf[..want]) {
+                Ok(0) if self.partial.is_empty() => return Ok(None),
+                Ok(0) => {
+                    let inside = if self.partial.len() < 2 { "a frame length" } else { "a frame" };
+                    self.partial.clear();
+                    return Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        format!("stream ended inside {}", inside),
+                    ));
+                }
+                Ok(n) => self.partial.extend_from_slice(&buf[..n]),
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    pub fn write_frame(&mut self, frame: &[u8]) -> io::Result<()> {
+        if frame.len() > self.max_frame_len {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("frame of {} bytes exceeds maximum of {}", frame.len(), self.max_frame_len),
+            ));
+        }
+        let mut buf = Vec::with_capacity(frame.len() + 2);
+        buf.extend_from_slice(&[(frame.len() >> 8) as u8, (frame.len() & 0xff) as u8]);
+        buf.extend_from_slice(frame);
+        self.inner.write_all(&buf)?;
+        self.inner.flush()
+    }
+}
+
+#[test]
+fn test_read_frames_from_short_reads() {
+    // Hands out at most one byte per read, like a very slow socket.
+    struct Trickle(io::Cursor<Vec<u8>>);
+    impl Read for Trickle {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let len = std::cmp::min(buf.len(), 1);
+            self.0.read(&mut buf[..len])
+        }
+    }
+    impl Write for Trickle {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.write(buf)
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let mut writer = FramedStream::new(io::Cursor::new(vec![]));
+    writer.write_frame(&[1, 2, 3]).unwrap();
+    writer.write_frame(&[]).unwrap();
+    writer.write_frame(&[4; 300]).unwrap();
+    let bytes = writer.into_inner().into_inner();
+
+    let mut reader = FramedStream::new(Trickle(io::Cursor::new(bytes)));
+    assert_eq!(reader.read_frame().unwrap(), Some(vec![1, 2, 3]));
+    assert_eq!(reader.read_frame().unwrap(), Some(vec![]));
+    assert_eq!(reader.read_frame().unwrap(), Some(vec![4; 300]));
+    assert_eq!(reader.read_frame().unwrap(), None);
+}
+
+#[test]
+fn test_read_frame_errors() {
+    let mut truncated = FramedStream::new(io::Cursor::new(vec![0, 3, 1, 2]));
+    let err = truncated.read_frame().unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+
+    let mut half_length = FramedStream::new(io::Cursor::new(vec![0]));
+    let err = half_length.read_frame().unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+
+    let mut too_long = FramedStream::with_max_frame_len(io::Cursor::new(vec![1, 0]), 255);
+    let err = too_long.read_frame().unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    assert!(too_long.write_frame(&[0; 256]).is_err());
+}
+
+#[test]
+fn test_read_frame_resumes_after_error() {
+    // Fails every other read, like a socket with a short read timeout.
+    struct Flaky(io::Cursor<Vec<u8>>, bool);
+    impl Read for Flaky {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.1 = !self.1;
+            if self.1 {
+                return Err(io::Error::new(io::ErrorKind::WouldBlock, "timed out"));
+            }
+            let len = std::cmp::min(buf.len(), 2);
+            self.0.read(&mut buf[..len])
+        }
+    }
+    impl Write for Flaky {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.write(buf)
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let mut reader = FramedStream::new(Flaky(io::Cursor::new(vec![0, 3, 1, 2, 3, 0, 1, 4]), false));
+    let mut frames = vec![];
+    while frames.len() < 2 {
+        match reader.read_frame() {
+            Ok(frame) => frames.push(frame.unwrap()),
+            Err(e) => assert_eq!(e.kind(), io::ErrorKind::WouldBlock),
+        }
+    }
+    assert_eq!(frames, vec![vec![1, 2, 3], vec![4]]);
+}
@@ -0,0 +1,242 @@
+use crate::messages::{tx_json, Header, KeyBlock, Message, MicroBlock, P2PResponse, Ping, SignedTx};
+use crate::rlp_val::transaction_hash;
+use rlp::Rlp;
+
+/*
+ * Receives decoded messages from `handle_message`. Every callback does
+ * nothing by default, so a handler only implements what it is interested
+ * in. Decoded values are handed over, so they can be kept without copying.
+ */
+pub trait MessageHandler {
+    /// Every message, before it is decoded.
+    fn on_raw(&mut self, _msg_type: u16, _body: &[u8]) {}
+
+    fn on_ping(&mut self, _ping: Ping) {}
+
+    /// The answer to a request: its object, decoded, or the reason the
+    /// peer gave for failing it.
+    fn on_p2p_response(&mut self, _response: P2PResponse<Message>) {}
+
+    fn on_header(&mut self, _header: Header) {}
+
+    /// Heights and hashes, as sent in answer to GetNSuccessors.
+    fn on_header_hashes(&mut self, _hashes: Vec<(u64, Vec<u8>)>) {}
+
+    fn on_key_block(&mut self, _key_block: KeyBlock) {}
+
+    fn on_micro_block(&mut self, _micro_block: MicroBlock) {}
+
+    fn on_generation(
+        &mut self,
+        _key_block: KeyBlock,
+        _micro_blocks: Vec<MicroBlock>,
+        _forward: bool,
+    ) {
+    }
+
+    /// Gossiped transactions, with their hashes and JSON form.
+    fn on_txs(&mut self, _txs: Vec<SignedTx>) {}
+
+    /// The transactions of the micro block with hash `hash`, as asked for
+    /// with GetBlockTxs.
+    fn on_block_txs(&mut self, _hash: Vec<u8>, _txs: Vec<SignedTx>) {}
+
+    /// Requests, tx pool sync and close messages.
+    fn on_message(&mut self, _msg: Message) {}
+
+    /// A message type this crate doesn't know, left undecoded.
+    fn on_unknown(&mut self, _msg_type: u16, _body: &[u8]) {}
+}
+
+/*
+ * Prints each message to stdout: its raw bytes, an ASCII dump and its RLP
+ * structure, followed by the decoded values.
+ */
+#[derive(Debug, Default)]
+pub struct DebugHandler;
+
+impl MessageHandler for DebugHandler {
+    fn on_raw(&mut self, msg_type: u16, body: &[u8]) {
+        println!("\n\n\nMsg type: {}", msg_type);
+        println!("Msg: {:?}", body);
+        let ascii: String = body
+            .iter()
+            .map(|x| if *x > 32u8 && *x < 127u8 { *x as char } else { '-' })
+            .collect();
+        println!("Received: {}", ascii);
+        display_message(&Rlp::new(body));
+    }
+
+    fn on_ping(&mut self, ping: Ping) {
+        println!("ping: {:?}", ping);
+    }
+
+    fn on_p2p_response(&mut self, response: P2PResponse<Message>) {
+        match response.result() {
+            Ok(msg) => println!("p2p_response: type: {} object: {:?}", response.msg_type(), msg),
+            Err(reason) => {
+                println!("p2p_response: type: {} failed: {}", response.msg_type(), reason)
+            }
+        }
+    }
+
+    fn on_header(&mut self, header: Header) {
+        match header {
+            Header::Key(key_block) => self.on_key_block(key_block),
+            Header::Micro(header) => {
+                println!("{} {}", header.id(), header.to_string().unwrap_or_default())
+            }
+        }
+    }
+
+    fn on_header_hashes(&mut self, hashes: Vec<(u64, Vec<u8>)>) {
+        for (height, hash) in hashes {
+            println!("height: {} hash: {:?}", height, hash);
+        }
+    }
+
+    fn on_key_block(&mut self, key_block: KeyBlock) {
+        println!("{} {}", key_block.id(), key_block.to_string().unwrap_or_default());
+    }
+
+    fn on_micro_block(&mut self, micro_block: MicroBlock) {
+        let header = micro_block.header();
+        println!("{} {}", header.id(), header.to_string().unwrap_or_default());
+        print_txs(micro_block.txs(), micro_block.is_light());
+    }
+
+    fn on_generation(&mut self, key_block: KeyBlock, micro_blocks: Vec<MicroBlock>, forward: bool) {
+        println!("generation, forward: {}", forward);
+        self.on_key_block(key_block);
+        for micro_block in micro_blocks {
+            self.on_micro_block(micro_block);
+        }
+    }
+
+    fn on_txs(&mut self, txs: Vec<SignedTx>) {
+        print_signed_txs(&txs);
+    }
+
+    fn on_block_txs(&mut self, hash: Vec<u8>, txs: Vec<SignedTx>) {
+        println!("txs of block {:?}", hash);
+        print_signed_txs(&txs);
+    }
+
+    fn on_message(&mut self, msg: Message) {
+        println!("{:?}", msg);
+    }
+
+    fn on_unknown(&mut self, msg_type: u16, _body: &[u8]) {
+        println!("Unknown message type {}", msg_type);
+    }
+}
+
+// Light micro blocks carry transaction hashes instead of transactions.
+fn print_txs(txs: &[Vec<u8>], light: bool) {
+    for tx in txs {
+        if light {
+            println!("{}", transaction_hash(tx));
+            continue;
+        }
+        match tx_json(tx) {
+            Ok(json) => println!("{}", json),
+            Err(e) => println!("Undecodable transaction: {}", e),
+        }
+    }
+}
+
+fn print_signed_txs(txs: &[SignedTx]) {
+    for tx in txs {
+        match tx.json() {
+            Some(json) => println!("{} {}", tx.id(), json),
+            None => println!("{} undecodable", tx.id()),
+        }
+    }
+}
+
+fn display_message(msg_data: &Rlp) {
+    println!("Starting message with {} elements:", msg_data.item_count().unwrap_or(0));
+    for ele in msg_data.iter() {
+        match ele.prototype() {
+            Ok(rlp::Prototype::Data(size)) => {
+                println!("Data, size is {} content is {:?}", size, ele.data().unwrap_or(&[]))
+            }
+            Ok(rlp::Prototype::List(count)) => println!("List, length is {}", count),
+            _ => println!("Something else"),
+        };
+    }
+    println!("End message");
+}
+
+#[test]
+fn test_handle_messages() {
+    use crate::messages::{handle_message, MSG_GETGENERATION, MSG_KEYBLOCK, MSG_MICROBLOCK};
+    use crate::messages::{MSG_P2PRESPONSE, MSG_PING, MSG_TXS};
+
+    #[derive(Default)]
+    struct Collector {
+        raw: usize,
+        key_blocks: Vec<KeyBlock>,
+        micro_blocks: Vec<MicroBlock>,
+        txs: Vec<SignedTx>,
+        responses: Vec<P2PResponse<Message>>,
+        unknown: Vec<u16>,
+    }
+
+    impl MessageHandler for Collector {
+        fn on_raw(&mut self, _msg_type: u16, _body: &[u8]) {
+            self.raw += 1;
+        }
+        fn on_key_block(&mut self, key_block: KeyBlock) {
+            self.key_blocks.push(key_block);
+        }
+        fn on_micro_block(&mut self, micro_block: MicroBlock) {
+            self.micro_blocks.push(micro_block);
+        }
+        fn on_txs(&mut self, txs: Vec<SignedTx>) {
+            self.txs.extend(txs);
+        }
+        fn on_p2p_response(&mut self, response: P2PResponse<Message>) {
+            self.responses.push(response);
+        }
+        fn on_unknown(&mut self, msg_type: u16, _body: &[u8]) {
+            self.unknown.push(msg_type);
+        }
+    }
+
+    let mut messages = vec![
+        (MSG_KEYBLOCK, include!("../data/key-block.rs")),
+        (MSG_MICROBLOCK, include!("../data/micro-block.rs")),
+        (42, Rlp::new(&[0xc0])),
+    ];
+    for tx in include!("../data/transactions.rs") {
+        messages.push((MSG_TXS, tx));
+    }
+    let ping = Ping::new(3015, 1, vec![7; 32], 1, vec![9; 32], false, vec![]);
+    let pong = ping.response_to(&ping).unwrap();
+    let failed = crate::messages::p2p_response(MSG_GETGENERATION, Err("Not found")).unwrap();
+    messages.push((MSG_P2PRESPONSE, Rlp::new(&pong[2..])));
+    messages.push((MSG_P2PRESPONSE, Rlp::new(&failed[2..])));
+
+    let mut collector = Collector::default();
+    for (msg_type, msg_data) in messages.iter() {
+        handle_message(&mut collector, *msg_type, msg_data).unwrap();
+        handle_message(&mut DebugHandler, *msg_type, msg_data).unwrap();
+    }
+    assert_eq!(collector.raw, messages.len());
+    assert_eq!(collector.key_blocks.len(), 1);
+    assert_eq!(collector.micro_blocks.len(), 1);
+    assert!(collector.micro_blocks[0].is_light());
+    assert!(!collector.txs.is_empty());
+    for tx in &collector.txs {
+        assert_eq!(tx.hash(), &crate::rlp_val::blake2b_256(tx.bytes()));
+        assert!(tx.id().starts_with("th_"));
+        assert!(tx.json().is_some());
+    }
+    assert_eq!(collector.responses, vec![
+        P2PResponse::new(MSG_PING, Ok(Message::Ping(ping))),
+        P2PResponse::new(MSG_GETGENERATION, Err(String::from("Not found"))),
+    ]);
+    assert_eq!(collector.unknown, vec![42]);
+    assert!(handle_message(&mut collector, MSG_KEYBLOCK, &Rlp::new(&[0xc1, 0x02])).is_err());
+}
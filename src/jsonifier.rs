@@ -1,9 +1,8 @@
 use serde::ser::{Serialize, Serializer};
 use serde_json::Value;
+use crate::error::Error;
 use crate::rlp_val::*;
 
-type RlpError = Box<std::error::Error>;
-
 const OBJECT_TAG_SIGNED_TRANSACTION: u32 = 11;
 const OBJECT_TAG_SPEND_TRANSACTION: u32 = 12;
 const OBJECT_TAG_ORACLE_REGISTER_TRANSACTION: u32 = 22;
@@ -83,16 +82,16 @@ impl Serialize for TxType {
     }
 }
 
-pub fn process_tx(tag: u32, stx: &RlpVal) -> Value {
+pub fn process_tx(tag: u32, stx: &RlpVal) -> Result<Value, Error> {
     match TxType::from_tag(tag) {
-        Some(txType) => parse_tx(stx, txType),
-        None => panic!("Wrong Transaction type")
+        Some(tx_type) => parse_tx(stx, tx_type),
+        None => Err(Error::UnknownTag { what: "transaction tag", tag: u64::from(tag) }),
     }
 }
 
-fn parse_tx(stx: &RlpVal, tx_type: TxType) -> Value {
-    match tx_type {
-        TxType::Signed => signed_tx(stx).unwrap(), // TODO
+fn parse_tx(stx: &RlpVal, tx_type: TxType) -> Result<Value, Error> {
+    Ok(match tx_type {
+        TxType::Signed => signed_tx(stx)?,
         TxType::Spend => spend_tx(stx),
         TxType::ContractCall => contract_call(stx),
         TxType::ContractCreate => contract_create(stx),
@@ -105,17 +104,17 @@ fn parse_tx(stx: &RlpVal, tx_type: TxType) -> Value {
         TxType::OracleExtend => oracle_extend(stx),
         TxType::OracleQuery => oracle_query(stx),
         TxType::OracleRespond => oracle_respond(stx),
-    }
+    })
 }
 
-pub fn signed_tx(stx: &RlpVal) -> ::std::result::Result<Value, RlpError>
+pub fn signed_tx(stx: &RlpVal) -> Result<Value, Error>
 {
     let tx_rlp_val = match &stx[3] {
-        RlpVal::Val { data } => rlp::Rlp::new(&data),
+        RlpVal::Val { data } => rlp::Rlp::new(data),
         _ => return Err("Wrong type of RlpVal".into()),
     };
     let tx = RlpVal::from_rlp(&tx_rlp_val)?;
-    let tx_json = process_tx(u32::convert(&tx[0]), &tx);
+    let tx_json = process_tx(u32::convert(&tx[0]), &tx)?;
     Ok(json!(
         {
             "type": TxType::Signed,
@@ -126,7 +125,6 @@ pub fn signed_tx(stx: &RlpVal) -> ::std::result::Result<Value, RlpError>
 
 pub fn spend_tx(rlp: &RlpVal) -> Value
 {
-    println!("spend_tx: {:?}", rlp);
     json!(
         {
             "fee": u64::convert(&rlp[5]),
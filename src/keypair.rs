@@ -0,0 +1,127 @@
+use crate::aenode::decodebase58check;
+use crate::error::Error;
+use crate::rlp_val::to_base58check;
+use crate::PARAMS;
+use crypto::curve25519::curve25519_base;
+use snow::{Builder, Keypair};
+use std::fs::{self, OpenOptions};
+use std::io::{Read, Write};
+use std::path::Path;
+
+const KEY_LEN: usize = 32;
+
+/*
+ * Our static Curve25519 noise keypair. Peers know a node by the public
+ * half, written as pp_ followed by its base58check encoding, so keeping
+ * the same key across restarts keeps the same identity.
+ *
+ * On disk the key is just the 32 private key bytes, readable only by the
+ * owner; the public key is derived from it when loading.
+ */
+pub fn generate() -> Result<Keypair, Error> {
+    let keypair = Builder::new(PARAMS.clone())
+        .generate_keypair()
+        .map_err(|e| Error::Invalid(format!("Key generation failed: {}", e)))?;
+    Ok(keypair)
+}
+
+/// Build the keypair belonging to a private key.
+pub fn from_private(private: &[u8]) -> Result<Keypair, Error> {
+    if private.len() != KEY_LEN {
+        bail!("Private key must be {} bytes, not {}", KEY_LEN, private.len());
+    }
+    Ok(Keypair {
+        private: private.to_vec(),
+        public: curve25519_base(private).to_vec(),
+    })
+}
+
+/// Write the private key to `path`, creating or replacing the file with
+/// permissions that only let its owner read it.
+pub fn save(keypair: &Keypair, path: &Path) -> Result<(), Error> {
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+        options.mode(0o600);
+        // mode() only applies to new files, so tighten an existing one
+        // before writing the key into it.
+        if path.exists() {
+            fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+        }
+    }
+    let mut file = options.open(path)?;
+    file.write_all(&keypair.private)?;
+    file.sync_all()?;
+    Ok(())
+}
+
+/// Read a private key written by `save`. Keys that other users could read
+/// are refused, as they can't be trusted to still be ours alone.
+pub fn load(path: &Path) -> Result<Keypair, Error> {
+    let mut file = fs::File::open(path)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = file.metadata()?.permissions().mode();
+        if mode & 0o077 != 0 {
+            bail!("Key file {} is accessible by others (mode {:o})", path.display(), mode & 0o777);
+        }
+    }
+    let mut private = vec![];
+    file.read_to_end(&mut private)?;
+    from_private(&private)
+}
+
+/// Load the key at `path`, generating and saving a new one if there is
+/// no file there yet.
+pub fn load_or_generate(path: &Path) -> Result<Keypair, Error> {
+    if path.exists() {
+        return load(path);
+    }
+    let keypair = generate()?;
+    save(&keypair, path)?;
+    Ok(keypair)
+}
+
+/// Encode a public key as pp_...
+pub fn to_pp(public: &[u8]) -> String {
+    format!("pp_{}", to_base58check(public))
+}
+
+/// Decode a pp_... peer key into the raw key the handshake needs.
+pub fn decode_pp(pp: &str) -> Result<[u8; KEY_LEN], Error> {
+    if !pp.starts_with("pp_") {
+        bail!("Peer key {} does not start with pp_", pp);
+    }
+    decode_public_key(&pp[3..])
+}
+
+/// Decode the base58check part of a peer key, as held by `Aenode::pub_key`.
+pub fn decode_public_key(encoded: &str) -> Result<[u8; KEY_LEN], Error> {
+    let bytes = decodebase58check(encoded)?;
+    if bytes.len() != KEY_LEN {
+        bail!("Peer key must be {} bytes, not {}", KEY_LEN, bytes.len());
+    }
+    let mut key = [0u8; KEY_LEN];
+    key.copy_from_slice(&bytes);
+    Ok(key)
+}
+
+#[test]
+fn test_keypair_roundtrip() {
+    let keypair = generate().unwrap();
+    let path = std::env::temp_dir().join(format!("creak-test-key-{}", std::process::id()));
+    save(&keypair, &path).unwrap();
+    let loaded = load_or_generate(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+    assert_eq!(loaded.private, keypair.private);
+    assert_eq!(loaded.public, keypair.public);
+
+    let pp = to_pp(&keypair.public);
+    assert!(pp.starts_with("pp_"));
+    assert_eq!(decode_pp(&pp).unwrap().to_vec(), keypair.public);
+    assert!(decode_pp(&pp[3..]).is_err());
+    assert!(decode_pp("pp_2pj2ew").is_err());
+}
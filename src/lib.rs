@@ -17,55 +17,43 @@ extern crate simple_error;
 extern crate hex;
 extern crate snow;
 
-use base58check::FromBase58Check;
 use byteorder::{BigEndian, ByteOrder};
+use crate::handler::MessageHandler;
 use snow::params::NoiseParams;
-use snow::Builder;
-use std::io::{self, Read, Write};
-use std::net::TcpStream;
 
-use serde_rlp::ser::to_bytes;
+pub use crate::error::Error;
 
-mod aenode;
+pub mod aenode;
+#[cfg(feature = "async")]
+pub mod async_connection;
+pub mod chain;
+pub mod connection;
+pub mod error;
+pub mod fragment;
+pub mod frame;
+pub mod handler;
 pub mod jsonifier;
+pub mod keypair;
+pub mod listener;
 pub mod messages;
+pub mod pow;
 pub mod rlp_val;
+pub mod sync;
 
 lazy_static! {
     static ref PARAMS: NoiseParams = "Noise_XK_25519_ChaChaPoly_BLAKE2b".parse().unwrap();
 }
 
-pub fn handle_message(data: &[u8], len: usize) {
-    let msg_type = BigEndian::read_u16(&data[0..2]);
-    let msg = rlp::Rlp::new(&data[2..len]);
-    println!("Msg type: {}", msg_type);
-    println!("Msg: {:?}", msg.as_raw());
-    let mut x = String::from("");
-    for item in data.iter().take(len).skip(2) {
-        if *item > 32u8 && *item < 127u8 {
-            x.push(*item as char);
-        } else {
-            x.push('-');
-        }
+/// Decode a message as received from a peer, its 16-bit BE message type
+/// followed by the RLP body, and pass it to `handler`. Use
+/// `handler::DebugHandler` to print it instead.
+pub fn handle_message<H: MessageHandler + ?Sized>(
+    handler: &mut H,
+    data: &[u8],
+) -> Result<(), Error> {
+    if data.len() < 2 {
+        return Err(Error::Truncated { what: "Message", len: data.len(), needed: 2 });
     }
-    println!("Received: {}", x);
-    messages::handle_message(msg_type, &msg).unwrap();
-    println!("\n\n\n");
-}
-
-/// Hyper-basic stream transport sender. 16-bit BE size followed by payload.
-fn send(stream: &mut TcpStream, buf: &[u8]) {
-    let msg_len_buf = [(buf.len() >> 8) as u8, (buf.len() & 0xff) as u8];
-    stream.write_all(&msg_len_buf).unwrap();
-    stream.write_all(buf).unwrap();
-}
-
-/// Hyper-basic stream transport receiver. 16-bit BE size followed by payload.
-fn recv(stream: &mut TcpStream) -> io::Result<Vec<u8>> {
-    let mut msg_len_buf = [0u8; 2];
-    let mut _read = stream.read(&mut msg_len_buf)?;
-    let msg_len = ((msg_len_buf[0] as usize) << 8) + (msg_len_buf[1] as usize);
-    let mut msg = vec![0u8; msg_len];
-    _read = stream.read(&mut msg[..])?;
-    Ok(msg)
+    let msg_type = BigEndian::read_u16(&data[0..2]);
+    messages::handle_message(handler, msg_type, &rlp::Rlp::new(&data[2..]))
 }
@@ -0,0 +1,117 @@
+use crate::aenode;
+use crate::connection::PeerConnection;
+use crate::error::Error;
+use snow::Keypair;
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+
+/*
+ * Accepts connections from peers and runs the responder side of the
+ * Noise_XK handshake with our static key. Peers find us through the
+ * pp_ key and port we advertise in Ping, and must be on the same chain
+ * and network: their prologue has to match ours.
+ */
+pub struct PeerListener {
+    listener: TcpListener,
+    static_keypair: Keypair,
+    prologue: Vec<u8>,
+}
+
+impl PeerListener {
+    /// Listen on `addr` for peers of the chain starting at `genesis_hash`
+    /// (base58check, without the kh_ prefix) on network `network_id`.
+    pub fn bind<A: ToSocketAddrs>(
+        addr: A,
+        static_keypair: Keypair,
+        genesis_hash: &str,
+        network_id: &str,
+    ) -> Result<PeerListener, Error> {
+        let prologue = aenode::prologue(1, genesis_hash, network_id)?;
+        PeerListener::bind_with_prologue(addr, static_keypair, prologue)
+    }
+
+    /// Listen on `addr` for peers using an already known prologue.
+    pub fn bind_with_prologue<A: ToSocketAddrs>(
+        addr: A,
+        static_keypair: Keypair,
+        prologue: Vec<u8>,
+    ) -> Result<PeerListener, Error> {
+        Ok(PeerListener {
+            listener: TcpListener::bind(addr)?,
+            static_keypair,
+            prologue,
+        })
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr, Error> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    /// Wait for the next peer and complete its handshake. An error here
+    /// concerns that one peer only; the listener can keep accepting. The
+    /// handshake runs here, so a peer that stalls holds up the next ones
+    /// for up to five seconds; see `accept_stream` to avoid that.
+    pub fn accept(&self) -> Result<(PeerConnection, SocketAddr), Error> {
+        let (stream, addr) = self.accept_stream()?;
+        Ok((self.handshake(stream)?, addr))
+    }
+
+    /// Wait for the next peer, leaving its handshake to `handshake`, e.g.
+    /// on a thread of its own.
+    pub fn accept_stream(&self) -> Result<(TcpStream, SocketAddr), Error> {
+        Ok(self.listener.accept()?)
+    }
+
+    /// Complete the handshake of a peer from `accept_stream`.
+    pub fn handshake(&self, stream: TcpStream) -> Result<PeerConnection, Error> {
+        PeerConnection::accept(stream, &self.static_keypair, &self.prologue)
+    }
+}
+
+#[test]
+fn test_accept_checks_prologue() {
+    use crate::aenode::Aenode;
+    use crate::keypair;
+    use crate::messages::{Message, PeerInfo, Ping};
+
+    fn ping(share: u16) -> Ping {
+        let peer = PeerInfo::new(String::from("10.0.0.1"), 3015, [1; 32]);
+        Ping::new(3015, share, vec![7; 32], 1, vec![9; 32], true, vec![peer])
+    }
+
+    let listener_keys = keypair::generate().unwrap();
+    let listener_public = listener_keys.public.clone();
+    let listener =
+        PeerListener::bind_with_prologue("127.0.0.1:0", listener_keys, b"mainnet".to_vec());
+    let listener = listener.unwrap();
+    let aenode = Aenode {
+        pub_key: crate::rlp_val::to_base58check(&listener_public),
+        address: listener.local_addr().unwrap().ip(),
+        port: listener.local_addr().unwrap().port(),
+    };
+
+    let peer = std::thread::spawn(move || {
+        // Connects first but never starts its handshake.
+        let silent = std::net::TcpStream::connect((aenode.address, aenode.port)).unwrap();
+        let keys = keypair::generate().unwrap();
+        let public = keys.public.clone();
+        let mut conn = PeerConnection::connect_with_prologue(&aenode, &keys, b"mainnet").unwrap();
+        conn.send_message(&ping(8).rlp().unwrap()).unwrap();
+        let response = conn.recv_message().unwrap().unwrap();
+        assert_eq!(&response[0..2], &[0, 100]);
+        assert!(PeerConnection::connect_with_prologue(&aenode, &keys, b"testnet").is_err());
+        (public, silent)
+    });
+
+    let (silent, _) = listener.accept_stream().unwrap();
+    let (stream, _) = listener.accept_stream().unwrap();
+    let mut conn = listener.handshake(stream).unwrap();
+    conn.answer_pings(ping(0));
+    match conn.recv().unwrap().unwrap() {
+        Message::Ping(ping) => assert_eq!(ping.share(), 8),
+        msg => panic!("Expected a ping, got {:?}", msg),
+    }
+    assert!(listener.accept().is_err());
+    let (public, _) = peer.join().unwrap();
+    assert_eq!(conn.remote_static().unwrap(), &public[..]);
+    assert!(listener.handshake(silent).is_err());
+}
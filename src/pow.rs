@@ -0,0 +1,452 @@
+use crate::messages::Header;
+use crate::rlp_val::blake2b_256;
+use base64::encode as to_base64;
+use byteorder::{BigEndian, ByteOrder, LittleEndian};
+use std::fmt;
+use std::iter::Sum;
+use std::ops::{Add, AddAssign};
+
+/// Edge bits of the mainnet and testnet Cuckoo Cycle graphs.
+pub const EDGE_BITS: u32 = 29;
+
+/// Edges in a solution, each a 32-bit big-endian nonce in the key header.
+pub const PROOF_SIZE: usize = 42;
+
+/// Cuckoo hashes an 80 byte header into its siphash keys.
+const CUCKOO_HEADER_SIZE: usize = 80;
+
+/*
+ * æternity's proof of work is Cuckoo Cycle: the header nonce picks a
+ * bipartite graph with 2^edge_bits edges, and the pow field lists the 42
+ * edges of a cycle in it. This follows the node's aeminer_pow_cuckoo:
+ *
+ * 1. Hash the key header, with its nonce and pow zeroed, with BLAKE2b.
+ * 2. The cuckoo header is the base64 of that hash followed by the base64
+ *    of the little-endian nonce, padded with zeroes to 80 bytes.
+ * 3. The BLAKE2b hash of the cuckoo header, read as four little-endian
+ *    u64s, is the siphash-2-4 state for the edge endpoints.
+ * 4. Edge n joins node siphash(2n) in one half of the graph to node
+ *    siphash(2n + 1) in the other, both masked to edge_bits.
+ *
+ * Separately, the BLAKE2b hash of the solution must be below the target.
+ *
+ * The key blocks captured in data/ meet their targets but fail step 4's
+ * endpoint check, whichever way the header, nonce and keys above are
+ * varied: the derivation still differs from the miner's somewhere, so
+ * don't rely on `Valid` for chain blocks until those pass.
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PowCheck {
+    Valid,
+    /// An edge is bigger than the graph has edges.
+    EdgeTooBig,
+    /// The edges aren't listed in strictly ascending order.
+    EdgesNotAscending,
+    /// The endpoints don't pair up, so the edges can't form cycles.
+    EndpointsMismatch,
+    /// Three edges meet at one node.
+    Branch,
+    /// An edge leads to a node no other edge touches.
+    DeadEnd,
+    /// The edges form a cycle shorter than 42, plus something else.
+    ShortCycle,
+    /// The solution is a cycle, but its hash doesn't meet the target.
+    TargetNotMet,
+}
+
+impl PowCheck {
+    pub fn is_valid(self) -> bool {
+        self == PowCheck::Valid
+    }
+}
+
+/// Verify a solution. `header` is the serialized key header with nonce
+/// and pow set to zeroes, `target` the compact target from the header.
+pub fn verify(
+    header: &[u8],
+    nonce: u64,
+    solution: &[u32; PROOF_SIZE],
+    target: u32,
+    edge_bits: u32,
+) -> PowCheck {
+    if !meets_target(&solution_hash(solution), target) {
+        return PowCheck::TargetNotMet;
+    }
+    verify_cycle(&siphash_keys(&blake2b_256(header), nonce), solution, edge_bits)
+}
+
+/// Read the 42 edges from the pow field of a key header.
+pub fn solution_from_bytes(pow: &[u8; PROOF_SIZE * 4]) -> [u32; PROOF_SIZE] {
+    let mut solution = [0; PROOF_SIZE];
+    BigEndian::read_u32_into(pow, &mut solution);
+    solution
+}
+
+/// What the target is tested against: the BLAKE2b hash of the edges,
+/// sorted, as 32-bit big-endian integers.
+pub fn solution_hash(solution: &[u32; PROOF_SIZE]) -> [u8; 32] {
+    let mut sorted = *solution;
+    sorted.sort_unstable();
+    let mut bytes = [0; PROOF_SIZE * 4];
+    BigEndian::write_u32_into(&sorted, &mut bytes);
+    blake2b_256(&bytes)
+}
+
+/*
+ * The target is in the compact "scientific" format of aeminer_pow: the
+ * top byte is an exponent, the other 24 bits a significand, and the
+ * threshold is significand * 256^(exponent - 3). Bit 23 of the significand
+ * marks a negative exponent, for thresholds below 2^16.
+ */
+pub fn meets_target(hash: &[u8; 32], target: u32) -> bool {
+    hash < &target_threshold(target)
+}
+
+/// The threshold as a 256-bit big-endian integer, saturated at 2^256 - 1.
+pub fn target_threshold(target: u32) -> [u8; 32] {
+    let (exponent, significand) = split_target(target);
+    let mut threshold = [0; 32];
+    let shift = exponent - 3;
+    if shift < 0 {
+        let value = significand.checked_shr(8 * -shift as u32).unwrap_or(0);
+        threshold[28..].copy_from_slice(&value.to_be_bytes());
+        return threshold;
+    }
+    // Byte i of the significand, counting from the least significant,
+    // lands `shift` bytes further up.
+    for (i, &byte) in significand.to_be_bytes()[1..].iter().rev().enumerate() {
+        match 31usize.checked_sub(shift as usize + i) {
+            Some(at) => threshold[at] = byte,
+            // More than 256 bits: every hash meets it.
+            None if byte != 0 => return [0xff; 32],
+            None => (),
+        }
+    }
+    threshold
+}
+
+fn split_target(target: u32) -> (i32, u32) {
+    let exponent = (target >> 24) as i32;
+    let significand = target & 0x00ff_ffff;
+    if significand & 0x0080_0000 != 0 {
+        (-exponent, significand & 0x007f_ffff)
+    } else {
+        (exponent, significand)
+    }
+}
+
+/*
+ * Difficulty is how many times harder a target is than the easiest one,
+ * 0x2100ffff, scaled by 2^24 so it stays an integer:
+ *
+ *   difficulty = 2^24 * 0xffff * 2^240 / threshold
+ *
+ * The 2^24 and 2^240 are kept together as DIFFICULTY_SHIFT below. The
+ * node sums the difficulties of all key blocks since genesis to compare
+ * chains, and advertises that total in Ping.
+ */
+const HIGHEST_SIGNIFICAND: u128 = 0xffff;
+const DIFFICULTY_SHIFT: u32 = 24 + 240;
+
+/// A compact target, as in the key header.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Target(pub u32);
+
+impl Target {
+    /// The easiest target, that of genesis.
+    pub const HIGHEST: Target = Target(0x2100_ffff);
+
+    /// The target for a difficulty, rounded down to 24 bits of precision
+    /// as the node does. A difficulty of zero is taken as one.
+    pub fn from_difficulty(difficulty: Difficulty) -> Target {
+        let divisor = difficulty.0.max(1);
+        // Drop whole bytes from the threshold until it fits in 23 bits.
+        for dropped in 0..=DIFFICULTY_SHIFT / 8 {
+            let shift = DIFFICULTY_SHIFT - 8 * dropped;
+            match shl_div(HIGHEST_SIGNIFICAND, shift, divisor) {
+                Some(significand) if significand <= 0x007f_ffff => {
+                    return Target((3 + dropped) << 24 | significand as u32);
+                }
+                _ => (),
+            }
+        }
+        unreachable!("0xffff fits in 23 bits")
+    }
+
+    pub fn difficulty(self) -> Difficulty {
+        let (exponent, significand) = split_target(self.0);
+        let shift = 8 * (exponent - 3);
+        let (significand, shift) = if shift < 0 {
+            (significand.checked_shr(-shift as u32).unwrap_or(0), 0)
+        } else {
+            (significand, shift as u32)
+        };
+        if significand == 0 {
+            // Nothing meets a zero threshold.
+            return Difficulty(u128::MAX);
+        }
+        let significand = u128::from(significand);
+        if shift >= DIFFICULTY_SHIFT {
+            let quotient = HIGHEST_SIGNIFICAND.checked_shr(shift - DIFFICULTY_SHIFT).unwrap_or(0);
+            return Difficulty(quotient / significand);
+        }
+        Difficulty(shl_div(HIGHEST_SIGNIFICAND, DIFFICULTY_SHIFT - shift, significand)
+                   .unwrap_or(u128::MAX))
+    }
+
+    pub fn threshold(self) -> [u8; 32] {
+        target_threshold(self.0)
+    }
+
+    pub fn is_met_by(self, hash: &[u8; 32]) -> bool {
+        meets_target(hash, self.0)
+    }
+}
+
+impl From<u32> for Target {
+    fn from(target: u32) -> Target {
+        Target(target)
+    }
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:#010x}", self.0)
+    }
+}
+
+/// Difficulty of a block, or the total of a chain. Sums saturate at
+/// `u128::MAX` rather than overflowing.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+pub struct Difficulty(pub u128);
+
+impl Difficulty {
+    pub const ZERO: Difficulty = Difficulty(0);
+}
+
+impl From<u128> for Difficulty {
+    fn from(difficulty: u128) -> Difficulty {
+        Difficulty(difficulty)
+    }
+}
+
+impl From<Difficulty> for u128 {
+    fn from(difficulty: Difficulty) -> u128 {
+        difficulty.0
+    }
+}
+
+impl Add for Difficulty {
+    type Output = Difficulty;
+
+    fn add(self, other: Difficulty) -> Difficulty {
+        Difficulty(self.0.saturating_add(other.0))
+    }
+}
+
+impl AddAssign for Difficulty {
+    fn add_assign(&mut self, other: Difficulty) {
+        *self = *self + other;
+    }
+}
+
+impl Sum for Difficulty {
+    fn sum<I: Iterator<Item = Difficulty>>(iter: I) -> Difficulty {
+        iter.fold(Difficulty::ZERO, Add::add)
+    }
+}
+
+impl fmt::Display for Difficulty {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Total difficulty of a run of headers: the sum over its key blocks,
+/// micro blocks adding nothing. Over a chain from genesis this is what
+/// the node advertises in Ping, and the heavier of two tops wins.
+pub fn total_difficulty<'a, I: IntoIterator<Item = &'a Header>>(headers: I) -> Difficulty {
+    headers.into_iter().map(Header::difficulty).sum()
+}
+
+// a * 2^shift / divisor, rounded down, or None if it overflows. A bit at
+// a time, so the divisor may use all 128 bits.
+fn shl_div(a: u128, shift: u32, divisor: u128) -> Option<u128> {
+    let mut quotient = a / divisor;
+    let mut remainder = a % divisor;
+    for _ in 0..shift {
+        let carry = remainder >> 127 != 0;
+        remainder <<= 1;
+        quotient = quotient.checked_mul(2)?;
+        if carry || remainder >= divisor {
+            remainder = remainder.wrapping_sub(divisor);
+            quotient += 1;
+        }
+    }
+    Some(quotient)
+}
+
+fn siphash_keys(header_hash: &[u8; 32], nonce: u64) -> [u64; 4] {
+    let mut header = Vec::with_capacity(CUCKOO_HEADER_SIZE);
+    header.extend_from_slice(to_base64(header_hash).as_bytes());
+    header.extend_from_slice(to_base64(&nonce.to_le_bytes()).as_bytes());
+    header.resize(CUCKOO_HEADER_SIZE, 0);
+    let mut keys = [0; 4];
+    LittleEndian::read_u64_into(&blake2b_256(&header), &mut keys);
+    keys
+}
+
+/*
+ * Cuckoo's siphash-2-4: the 256-bit key is the whole initial state, rather
+ * than being mixed with constants as in standard SipHash.
+ */
+fn siphash24(keys: &[u64; 4], nonce: u64) -> u64 {
+    let mut v = *keys;
+    v[3] ^= nonce;
+    sip_round(&mut v);
+    sip_round(&mut v);
+    v[0] ^= nonce;
+    v[2] ^= 0xff;
+    for _ in 0..4 {
+        sip_round(&mut v);
+    }
+    v[0] ^ v[1] ^ v[2] ^ v[3]
+}
+
+fn sip_round(v: &mut [u64; 4]) {
+    v[0] = v[0].wrapping_add(v[1]);
+    v[2] = v[2].wrapping_add(v[3]);
+    v[1] = v[1].rotate_left(13);
+    v[3] = v[3].rotate_left(16);
+    v[1] ^= v[0];
+    v[3] ^= v[2];
+    v[0] = v[0].rotate_left(32);
+    v[2] = v[2].wrapping_add(v[1]);
+    v[0] = v[0].wrapping_add(v[3]);
+    v[1] = v[1].rotate_left(17);
+    v[3] = v[3].rotate_left(21);
+    v[1] ^= v[2];
+    v[3] ^= v[0];
+    v[2] = v[2].rotate_left(32);
+}
+
+// As in Cuckoo's verify(): find the endpoints, then walk the cycle.
+fn verify_cycle(keys: &[u64; 4], solution: &[u32; PROOF_SIZE], edge_bits: u32) -> PowCheck {
+    let edge_mask = (1u64 << edge_bits.min(32)) - 1;
+    // Node u of edge n is at 2n, node v at 2n + 1. The low bit keeps the
+    // two halves of the graph apart.
+    let mut uvs = [0u64; 2 * PROOF_SIZE];
+    let (mut xor0, mut xor1) = (0, 0);
+    for (n, &edge) in solution.iter().enumerate() {
+        let edge = u64::from(edge);
+        if edge > edge_mask {
+            return PowCheck::EdgeTooBig;
+        }
+        if n > 0 && edge <= u64::from(solution[n - 1]) {
+            return PowCheck::EdgesNotAscending;
+        }
+        let u = siphash24(keys, 2 * edge) & edge_mask;
+        let v = siphash24(keys, 2 * edge + 1) & edge_mask;
+        xor0 ^= u;
+        xor1 ^= v;
+        uvs[2 * n] = u << 1;
+        uvs[2 * n + 1] = v << 1 | 1;
+    }
+    if xor0 | xor1 != 0 {
+        return PowCheck::EndpointsMismatch;
+    }
+    let mut n = 0;
+    let mut i = 0;
+    loop {
+        // Find the one other edge sharing the node at i.
+        let mut j = i;
+        let mut k = i;
+        loop {
+            k = (k + 2) % (2 * PROOF_SIZE);
+            if k == i {
+                break;
+            }
+            if uvs[k] == uvs[i] {
+                if j != i {
+                    return PowCheck::Branch;
+                }
+                j = k;
+            }
+        }
+        if j == i {
+            return PowCheck::DeadEnd;
+        }
+        // Leave that edge by its other end.
+        i = j ^ 1;
+        n += 1;
+        if i == 0 {
+            break;
+        }
+    }
+    if n == PROOF_SIZE {
+        PowCheck::Valid
+    } else {
+        PowCheck::ShortCycle
+    }
+}
+
+#[test]
+fn test_target_threshold() {
+    let mut expected = [0; 32];
+    expected[2..5].copy_from_slice(&[0x08, 0xda, 0x79]);
+    assert_eq!(target_threshold(0x1e08_da79), expected);
+    let mut expected = [0; 32];
+    expected[29..].copy_from_slice(&[0x12, 0x34, 0x56]);
+    assert_eq!(target_threshold(0x0312_3456), expected);
+    let mut expected = [0; 32];
+    expected[30..].copy_from_slice(&[0x12, 0x34]);
+    assert_eq!(target_threshold(0x0212_3456), expected);
+    assert_eq!(target_threshold(0x0180_1234), [0; 32]);
+    let mut expected = [0; 32];
+    expected[..2].copy_from_slice(&[0xff, 0xff]);
+    assert_eq!(target_threshold(0x2100_ffff), expected);
+    assert_eq!(target_threshold(0x2200_ffff), [0xff; 32]);
+    assert_eq!(target_threshold(0xff00_0000), [0; 32]);
+
+    assert!(meets_target(&[0; 32], 0x1e08_da79));
+    assert!(!meets_target(&expected, 0x0212_3456));
+    assert!(!meets_target(&[0xff; 32], 0x2200_ffff));
+}
+
+#[test]
+fn test_siphash24() {
+    // With the standard SipHash initialisation of the key 0..16 this is
+    // the reference SipHash-2-4 of the empty message.
+    let (k0, k1) = (0x0706_0504_0302_0100u64, 0x0f0e_0d0c_0b0a_0908u64);
+    let keys = [
+        k0 ^ 0x736f_6d65_7073_6575,
+        k1 ^ 0x646f_7261_6e64_6f6d,
+        k0 ^ 0x6c79_6765_6e65_7261,
+        k1 ^ 0x7465_6462_7974_6573,
+    ];
+    assert_eq!(siphash24(&keys, 0), 0x726f_db47_dd0e_0e31);
+}
+
+#[test]
+fn test_target_difficulty() {
+    assert_eq!(Target::HIGHEST.difficulty(), Difficulty(1 << 24));
+    assert_eq!(Target(0x2200_ffff).difficulty(), Difficulty(1 << 16));
+    assert_eq!(Target(0x1e08_da79).difficulty(), Difficulty(31_792_351_135_407));
+    assert_eq!(Target(0x1500_ffff).difficulty(), Difficulty(1 << 120));
+    assert_eq!(Target(0x117f_ffff).difficulty(), Difficulty(u128::MAX));
+    assert_eq!(Target(0x0180_1234).difficulty(), Difficulty(u128::MAX));
+
+    for &target in &[0x2100_ffff, 0x2200_ffff, 0x1e08_da79, 0x207f_ffff, 0x1500_ffff] {
+        let target = Target(target);
+        assert_eq!(Target::from_difficulty(target.difficulty()), target);
+    }
+    assert_eq!(Target::from_difficulty(Difficulty(1)), Target(0x2400_ffff));
+    assert_eq!(Target::from_difficulty(Difficulty::ZERO), Target(0x2400_ffff));
+    assert_eq!(Target::from_difficulty(Difficulty(u128::MAX)), Target(0x1400_ffff));
+    assert_eq!(Target::from_difficulty(Difficulty(12_345_678_901_234_567_890)),
+               Target(0x1c01_7e81));
+
+    assert_eq!(Difficulty(u128::MAX) + Difficulty(1), Difficulty(u128::MAX));
+    let total: Difficulty = vec![Difficulty(1), Difficulty(2)].into_iter().sum();
+    assert_eq!(total, Difficulty(3));
+}
@@ -3,15 +3,18 @@ extern crate base64;
 use base64::encode as to_base64;
 use base58::ToBase58;
 use byteorder::*;
+use crypto::blake2b::Blake2b;
 use crypto::digest::Digest;
 use crypto::sha2::Sha256;
-use rlp::{Rlp};
+use rlp::{Rlp, RlpStream};
 use serde::ser::{Serialize, SerializeSeq, Serializer};
 use std::ops::{Index};
 use std::convert::From;
 use std::fmt;
+use crate::error::Error;
 
-type RlpError = Box<std::error::Error>;
+/// Deepest nesting of lists `RlpVal::from_rlp` reads.
+pub const MAX_RLP_DEPTH: usize = 64;
 
 #[derive(Debug)]
 pub enum RlpVal {
@@ -32,19 +35,25 @@ impl RlpVal {
      */
 
 
-    pub fn from_rlp(r: &Rlp) -> Result<RlpVal, RlpError>
+    pub fn from_rlp(r: &Rlp) -> Result<RlpVal, Error>
+    {
+        RlpVal::from_rlp_nested(r, 0)
+    }
+
+    // Transactions nest a few lists deep; a peer's data could nest deep
+    // enough to overflow the stack.
+    fn from_rlp_nested(r: &Rlp, depth: usize) -> Result<RlpVal, Error>
     {
         if r.is_list() {
-            println!("is_list");
+            if depth == MAX_RLP_DEPTH {
+                bail!("RLP nested deeper than {} lists", MAX_RLP_DEPTH);
+            }
             let mut data = Vec::<RlpVal>::new();
-            let mut iter = r.iter();
-            for x in iter {
-                println!("adding {:?}", x);
-                data.push(RlpVal::from_rlp(&x).unwrap());
+            for x in r.iter() {
+                data.push(RlpVal::from_rlp_nested(&x, depth + 1)?);
             }
             Ok(RlpVal::List { data })
         } else {
-            println!("not_list");
             Ok(  RlpVal::Val { data: r.data()?.to_vec() })
         }
     }
@@ -69,7 +78,7 @@ impl Serialize for RlpVal {
         match &self {
             RlpVal::List { data } => serializer.serialize_str(&format!("{:?}", data)),
             RlpVal::Val { data } => serializer.serialize_bytes(data),
-            RlpVal::None =>  serializer.serialize_str(&"".to_string()),
+            RlpVal::None =>  serializer.serialize_str(""),
         }
     }
 }
@@ -101,7 +110,7 @@ impl FromRlp for u128 {
     fn convert(item: &RlpVal) -> Self {
         match item {
             RlpVal::Val { data } => {
-                BigEndian::read_u128(&ensure_vec_len(&mut data.clone(), 16))
+                BigEndian::read_u128(ensure_vec_len(&mut data.clone(), 16))
             },
             _ => 0
         }
@@ -112,7 +121,7 @@ impl FromRlp for u64 {
     fn convert(item: &RlpVal) -> Self {
         match item {
             RlpVal::Val { data } => {
-                BigEndian::read_u64(&ensure_vec_len(&mut data.clone(), 8))
+                BigEndian::read_u64(ensure_vec_len(&mut data.clone(), 8))
             },
             _ => 0
         }
@@ -123,7 +132,7 @@ impl FromRlp for u32 {
     fn convert(item: &RlpVal) -> Self {
         match item {
             RlpVal::Val { data } => {
-                BigEndian::read_u32(&ensure_vec_len(&mut data.clone(), 4))
+                BigEndian::read_u32(ensure_vec_len(&mut data.clone(), 4))
             },
             _ => 0
         }
@@ -145,7 +154,7 @@ impl FromRlp for u16 {
     fn convert(item: &RlpVal) -> Self {
         match item {
             RlpVal::Val { data } => {
-                BigEndian::read_u16(&ensure_vec_len(&mut data.clone(), 2))
+                BigEndian::read_u16(ensure_vec_len(&mut data.clone(), 2))
             },
             _ => 0
         }
@@ -158,7 +167,7 @@ impl FromRlp for String {
             RlpVal::Val { data } => {
                 match String::from_utf8(data.to_vec()) {
                     Ok(x) => x,
-                    Err(e) => String::from(e.to_string()),
+                    Err(e) => e.to_string(),
                 }
             },
             _ => String::from(""),
@@ -170,10 +179,13 @@ impl FromRlp for AeIdentifier {
     fn convert(item: &RlpVal) -> Self {
         match item {
             RlpVal::Val { data } => {
-                return match AeIdentifier::from_bytes(data[0], &data[1..].to_vec()) {
+                if data.is_empty() {
+                    return AeIdentifier { id: String::from("") };
+                }
+                match AeIdentifier::from_bytes(data[0], &data[1..]) {
                     Some(x) => x,
                     None => AeIdentifier { id: String::from("")},
-                };
+                }
             },
             _ => AeIdentifier { id: String::from("") },
         }
@@ -186,8 +198,11 @@ pub struct AeIdentifier {
 }
 
 impl AeIdentifier {
-    pub fn from_bytes(prefix: u8, bytes: &Vec<u8>) -> Option<AeIdentifier>
+    pub fn from_bytes(prefix: u8, bytes: &[u8]) -> Option<AeIdentifier>
     {
+        if bytes.is_empty() {
+            return None;
+        }
         let prefix = match prefix {
             1   => "ak_",
             2   => "nm_",
@@ -201,8 +216,11 @@ impl AeIdentifier {
         Some(AeIdentifier{ id: format!("{}{}", prefix, to_base58check(&bytes[1..])) })
     }
 
-    pub fn transaction_identifier(bytes: &Vec<u8>) -> Option<AeIdentifier>
+    pub fn transaction_identifier(bytes: &[u8]) -> Option<AeIdentifier>
     {
+        if bytes.is_empty() {
+            return None;
+        }
         let prefix = "th_";
         Some(AeIdentifier{ id: format!("{}{}", prefix, to_base58check(&bytes[1..])) })
     }
@@ -225,9 +243,8 @@ impl Serialize for SignatureList {
     where
         S: Serializer,
     {
-        let mut seq = serializer.serialize_seq(None).unwrap(); // TODO
-        let mut iter = self.signatures.iter();
-        for x in iter {
+        let mut seq = serializer.serialize_seq(None)?;
+        for x in self.signatures.iter() {
             seq.serialize_element(x)?;
         }
         seq.end()
@@ -240,16 +257,14 @@ impl FromRlp for SignatureList {
         match item {
             RlpVal::List { data } => {
                 let mut v: Vec<AeIdentifier> = Vec::new();
-                let mut iter = data.iter();
-                loop {
-                    match iter.next() {
-                        Some(ele) => {
-                            match ele {
-                                RlpVal::Val { data } => v.push(AeIdentifier::from_bytes(data[0], &data[1..].to_vec()).unwrap()),
-                                _ => (),
+                for ele in data.iter() {
+                    match ele {
+                        RlpVal::Val { data } if !data.is_empty() => {
+                            if let Some(id) = AeIdentifier::from_bytes(data[0], &data[1..]) {
+                                v.push(id);
                             }
-                        },
-                        None => break,
+                        }
+                        _ => (),
                     }
                 }
                 SignatureList::new(v)
@@ -280,7 +295,7 @@ impl Index<usize> for RlpVal {
 
     fn index(&self, index: usize) -> &RlpVal {
         match self {
-            RlpVal::List { data } => &data[index],
+            RlpVal::List { data } => data.get(index).unwrap_or(&RlpVal::None),
             RlpVal::Val { .. }  => {
                 &RlpVal::None
             },
@@ -289,6 +304,62 @@ impl Index<usize> for RlpVal {
     }
 }
 
+/*
+ * æternity writes integers as minimal big-endian bytes (Erlang's
+ * binary:encode_unsigned), zero being a single 0 byte rather than RLP's
+ * empty string, and booleans as a single 0 or 1 byte. Hashes, keys
+ * and other byte arrays are appended with the usual `append`.
+ */
+pub trait AeRlpStream {
+    fn append_int<T: Into<u128>>(&mut self, value: T) -> &mut Self;
+    fn append_bool(&mut self, value: bool) -> &mut Self;
+}
+
+impl AeRlpStream for RlpStream {
+    fn append_int<T: Into<u128>>(&mut self, value: T) -> &mut Self {
+        self.append(&int_to_bytes(value.into()).as_slice())
+    }
+
+    fn append_bool(&mut self, value: bool) -> &mut Self {
+        self.append(&[value as u8].as_ref())
+    }
+}
+
+/// Minimal big-endian bytes of `value`; a single 0 byte for zero.
+pub fn int_to_bytes(value: u128) -> Vec<u8> {
+    let bytes = value.to_be_bytes();
+    let leading = (value.leading_zeros() / 8).min(15) as usize;
+    bytes[leading..].to_vec()
+}
+
+#[test]
+fn test_append_int() {
+    let mut stream = RlpStream::new_list(6);
+    stream.append_int(0u8)
+        .append_int(0x7fu8)
+        .append_int(0x80u8)
+        .append_int(0x8080u16)
+        .append_int(u128::from(u64::MAX) + 1)
+        .append_bool(false);
+    assert_eq!(stream.out(), vec![0xd2, 0x00, 0x7f, 0x81, 0x80, 0x82, 0x80, 0x80,
+                                  0x89, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0x00]);
+}
+
+#[test]
+fn test_from_rlp_depth() {
+    let nested = |depth: usize| {
+        let mut stream = RlpStream::new();
+        for _ in 0..depth {
+            stream.begin_list(1);
+        }
+        stream.append_empty_data();
+        stream.out()
+    };
+    assert!(RlpVal::from_rlp(&Rlp::new(&nested(MAX_RLP_DEPTH))).is_ok());
+    assert!(RlpVal::from_rlp(&Rlp::new(&nested(MAX_RLP_DEPTH + 1))).is_err());
+    assert!(RlpVal::from_rlp(&Rlp::new(&nested(10_000))).is_err());
+}
+
 pub fn transaction_hash(data: &[u8]) -> String {
     String::from("th_") + &to_base58check(data)
 }
@@ -299,14 +370,14 @@ pub fn transaction_hash(data: &[u8]) -> String {
  */
 pub fn to_base58check(data: &[u8]) -> String {
     let mut payload = data.to_vec();
-    let mut checksum = double_sha256(&payload);
+    let checksum = double_sha256(&payload);
     payload.append(&mut checksum[..4].to_vec());
     payload.to_base58()
 }
 
 pub fn to_base64check (data: &[u8]) -> String {
     let mut payload = data.to_vec();
-    let mut checksum = double_sha256(&payload);
+    let checksum = double_sha256(&payload);
     payload.append(&mut checksum[..4].to_vec());
     to_base64(&payload)
 }
@@ -318,7 +389,7 @@ pub fn to_base64check (data: &[u8]) -> String {
 fn double_sha256(payload: &[u8]) -> Vec<u8> {
     let mut hasher = Sha256::new();
     let mut hash = vec![0; hasher.output_bytes()];
-    hasher.input(&payload);
+    hasher.input(payload);
     hasher.result(&mut hash);
     hasher.reset();
     hasher.input(&hash);
@@ -326,6 +397,14 @@ fn double_sha256(payload: &[u8]) -> Vec<u8> {
     hash.to_vec()
 }
 
+/// The 32 byte BLAKE2b digest the node uses for block, transaction and
+/// object hashes.
+pub fn blake2b_256(data: &[u8]) -> [u8; 32] {
+    let mut hash = [0; 32];
+    Blake2b::blake2b(&mut hash, data, &[]);
+    hash
+}
+
 pub fn encode(item: &RlpVal, prefix: &str) -> String {
     let base64types = ["tx", "st", "ss", "pi", "ov", "or", "cb"];
     let encoded_value: String = match item {
@@ -0,0 +1,912 @@
+#[cfg(feature = "async")]
+use crate::async_connection::AsyncPeerConnection;
+use crate::connection::PeerConnection;
+use crate::error::Error;
+use crate::messages::*;
+use byteorder::{BigEndian, ByteOrder};
+use std::collections::{HashMap, VecDeque};
+use std::io;
+use std::time::{Duration, Instant};
+
+/// How long to wait for a peer to answer a request.
+pub const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Most unfolds or transactions asked for in one tx pool sync request.
+pub const TX_POOL_SYNC_BATCH: usize = 100;
+
+/// A tree of 32 byte hashes is no deeper than their 64 nibbles, the root
+/// aside.
+const MAX_UNFOLD_DEPTH: usize = 65;
+
+// The methods `SyncClient` and `AsyncSyncClient` share, written once: the
+// async client's are `async` and await what they call. Each client has its
+// own `request`, as they wait for answers differently.
+macro_rules! client_methods {
+    ($client:ident, $conn:ty $(, $async:tt, .$await:tt)?) => {
+        pub fn new(conn: $conn) -> $client {
+            $client::with_timeout(conn, DEFAULT_REQUEST_TIMEOUT)
+        }
+
+        pub fn with_timeout(conn: $conn, timeout: Duration) -> $client {
+            $client { conn, timeout, requests: Requests::default() }
+        }
+
+        pub fn connection(&mut self) -> &mut $conn {
+            &mut self.conn
+        }
+
+        pub fn into_inner(self) -> $conn {
+            self.conn
+        }
+
+        pub $($async)? fn get_header_by_hash(&mut self, hash: &[u8; 32]) -> Result<Header, Error> {
+            let request = Message::GetHeaderByHash { hash: hash.to_vec() };
+            header_response(self.request(&request)$(.$await)??)
+        }
+
+        /// The header at `height` on the chain ending in `top_hash`.
+        pub $($async)? fn get_header_by_height(
+            &mut self,
+            height: u64,
+            top_hash: &[u8; 32],
+        ) -> Result<Header, Error> {
+            let request = Message::GetHeaderByHeight { height, top_hash: top_hash.to_vec() };
+            header_response(self.request(&request)$(.$await)??)
+        }
+
+        /// Heights and hashes of up to `n` key blocks after `hash`, on the
+        /// chain ending in `target_hash`.
+        pub $($async)? fn get_n_successors(
+            &mut self,
+            hash: &[u8; 32],
+            target_hash: &[u8; 32],
+            n: u64,
+        ) -> Result<Vec<(u64, Vec<u8>)>, Error> {
+            let request = successors_request(hash, target_hash, n);
+            header_hashes_response(self.request(&request)$(.$await)??)
+        }
+
+        /// The generation starting at key block `key_hash` if `forward`, else
+        /// the one before it, in one round trip.
+        pub $($async)? fn get_generation(
+            &mut self,
+            key_hash: &[u8; 32],
+            forward: bool,
+        ) -> Result<Generation, Error> {
+            let request = Message::GetGeneration { hash: key_hash.to_vec(), forward };
+            generation_response(self.request(&request)$(.$await)??)
+        }
+
+        /// Those of the signed transactions of micro block `hash` that have the
+        /// given hashes.
+        pub $($async)? fn get_block_txs(
+            &mut self,
+            hash: &[u8; 32],
+            tx_hashes: &[Vec<u8>],
+        ) -> Result<Vec<Vec<u8>>, Error> {
+            let tx_hashes = tx_hashes.to_vec();
+            let request = Message::GetBlockTxs { hash: hash.to_vec(), tx_hashes };
+            block_txs_response(hash, self.request(&request)$(.$await)??)
+        }
+
+        /// The full micro block of a light one. Transactions `known` has, from
+        /// a mempool for instance, are used as they are; the others are
+        /// fetched from the peer in one request.
+        pub $($async)? fn resolve_micro_block<F>(
+            &mut self,
+            micro_block: &MicroBlock,
+            known: F,
+        ) -> Result<MicroBlock, Error>
+        where
+            F: FnMut(&[u8]) -> Option<Vec<u8>>,
+        {
+            self.run(ResolveMicroBlock::new(micro_block, known))$(.$await)?
+        }
+
+        /// Start a tx pool sync. The peer keeps a snapshot of its pool until
+        /// `tx_pool_sync_finish`.
+        pub $($async)? fn tx_pool_sync_init(&mut self) -> Result<(), Error> {
+            init_response(self.request(&Message::TxPoolSyncInit)$(.$await)??)
+        }
+
+        /// The peer's unfolds of those of `unfolds` that differ from its pool.
+        pub $($async)? fn tx_pool_sync_unfold(
+            &mut self,
+            unfolds: &[Unfold],
+        ) -> Result<Vec<Unfold>, Error> {
+            unfolds_response(self.request(&unfold_request(unfolds))$(.$await)??)
+        }
+
+        /// The signed transactions of the peer's pool that have the given
+        /// hashes.
+        pub $($async)? fn tx_pool_sync_get(
+            &mut self,
+            tx_hashes: &[Vec<u8>],
+        ) -> Result<Vec<Vec<u8>>, Error> {
+            let request = Message::TxPoolSyncGet(tx_hashes.to_vec());
+            pool_txs_response(self.request(&request)$(.$await)??)
+        }
+
+        /// End a tx pool sync, `done` if it went through.
+        pub $($async)? fn tx_pool_sync_finish(&mut self, done: bool) -> Result<(), Error> {
+            finish_response(self.request(&Message::TxPoolSyncFinish { done })$(.$await)??)
+        }
+
+        /// The signed transactions of the peer's whole pool that `known`
+        /// doesn't have, by hash: init, unfold the peer's tree down to its
+        /// leaves, get what we miss and finish.
+        pub $($async)? fn sync_tx_pool<F>(&mut self, known: F) -> Result<Vec<Vec<u8>>, Error>
+        where
+            F: FnMut(&[u8]) -> bool,
+        {
+            self.run(PoolSync::new(known))$(.$await)?
+        }
+
+        // Make the requests of `exchange` in turn, and tell the peer if we
+        // give up half way.
+        $($async)? fn run<E: Exchange>(&mut self, mut exchange: E) -> Result<E::Output, Error> {
+            loop {
+                let step = match exchange.next_request() {
+                    Ok(Some(request)) => {
+                        let answer = self.request(&request)$(.$await)?;
+                        answer.and_then(|answer| exchange.receive(answer))
+                    }
+                    Ok(None) => return exchange.finish(),
+                    Err(e) => Err(e),
+                };
+                if let Err(e) = step {
+                    if let Some(abort) = exchange.abort() {
+                        // Don't wait for the answer.
+                        if self.conn.send(&abort)$(.$await)?.is_ok() {
+                            self.requests.timed_out(response_type(&abort)?);
+                        }
+                    }
+                    return Err(e);
+                }
+            }
+        }
+
+        /// The next message the peer sent on its own, those that arrived
+        /// while we waited for answers first. Returns `Ok(None)` once the peer
+        /// has closed the connection.
+        pub $($async)? fn recv_message(&mut self) -> Result<Option<Vec<u8>>, Error> {
+            if let Some(msg) = self.requests.queued.pop_front() {
+                return Ok(Some(msg));
+            }
+            loop {
+                match self.conn.recv_message()$(.$await)?? {
+                    Some(msg) if self.requests.is_stale(&msg) => continue,
+                    msg => return Ok(msg),
+                }
+            }
+        }
+
+        pub $($async)? fn recv(&mut self) -> Result<Option<Message>, Error> {
+            match self.recv_message()$(.$await)?? {
+                Some(msg) => Ok(Some(Message::from_bytes(&msg)?)),
+                None => Ok(None),
+            }
+        }
+    };
+}
+
+/*
+ * Requests to a peer and their answers. The peer answers each request
+ * with a MSG_P2PRESPONSE whose type is that of the answer: MSG_HEADER for
+ * GetHeaderByHash and GetHeaderByHeight, MSG_HEADERHASHES for
+ * GetNSuccessors, and so on. Answers of one type come back in the order
+ * they were asked for, so the type is all we need to match them up, one
+ * request at a time.
+ *
+ * Anything else the peer sends while we wait, gossiped blocks and
+ * transactions for instance, is kept for `recv_message`. A request that
+ * times out may still be answered later; that answer is skipped when it
+ * arrives.
+ */
+pub struct SyncClient {
+    conn: PeerConnection,
+    timeout: Duration,
+    requests: Requests,
+}
+
+impl SyncClient {
+    client_methods!(SyncClient, PeerConnection);
+
+    /// Send a request and wait for its answer, decoded. A peer's failure
+    /// is an `Error::Peer`, no answer in time a `TimedOut` transport error.
+    /// The connection's own read timeout is put back once we have it.
+    pub fn request(&mut self, request: &Message) -> Result<Message, Error> {
+        let expected = response_type(request)?;
+        let read_timeout = self.conn.read_timeout()?;
+        self.conn.send(request)?;
+        let deadline = Instant::now() + self.timeout;
+        let result = self.wait_for(expected, deadline);
+        self.conn.set_read_timeout(read_timeout)?;
+        result
+    }
+
+    fn wait_for(&mut self, expected: u16, deadline: Instant) -> Result<Message, Error> {
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining == Duration::from_secs(0) {
+                return Err(self.requests.give_up(expected));
+            }
+            self.conn.set_read_timeout(Some(remaining))?;
+            let msg = match self.conn.recv_message() {
+                Err(Error::Transport(ref e)) if is_timeout(e) => continue,
+                msg => msg?,
+            };
+            if let Some(answer) = self.requests.receive(msg, expected) {
+                return answer;
+            }
+        }
+    }
+}
+
+/// `SyncClient` on an `AsyncPeerConnection`.
+#[cfg(feature = "async")]
+pub struct AsyncSyncClient {
+    conn: AsyncPeerConnection,
+    timeout: Duration,
+    requests: Requests,
+}
+
+#[cfg(feature = "async")]
+impl AsyncSyncClient {
+    client_methods!(AsyncSyncClient, AsyncPeerConnection, async, .await);
+
+    /// Send a request and wait for its answer, decoded. A peer's failure
+    /// is an `Error::Peer`, no answer in time a `TimedOut` transport error.
+    pub async fn request(&mut self, request: &Message) -> Result<Message, Error> {
+        let expected = response_type(request)?;
+        self.conn.send(request).await?;
+        let deadline = tokio::time::Instant::now() + self.timeout;
+        loop {
+            let msg = match tokio::time::timeout_at(deadline, self.conn.recv_message()).await {
+                Ok(msg) => msg?,
+                Err(_) => return Err(self.requests.give_up(expected)),
+            };
+            if let Some(answer) = self.requests.receive(msg, expected) {
+                return answer;
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+struct Requests {
+    /// Messages that weren't the answer we waited for.
+    queued: VecDeque<Vec<u8>>,
+    /// Answers still to come to requests that timed out, by type.
+    stale: HashMap<u16, usize>,
+}
+
+impl Requests {
+    // Our answer if `msg` is it, decoded; otherwise keep it for later. No
+    // message means the peer closed the connection.
+    fn receive(&mut self, msg: Option<Vec<u8>>, expected: u16) -> Option<Result<Message, Error>> {
+        let msg = match msg {
+            Some(msg) => msg,
+            None => return Some(Err(closed())),
+        };
+        if self.is_stale(&msg) {
+            return None;
+        }
+        if p2p_response_type(&msg) != Some(expected) {
+            self.queued.push_back(msg);
+            return None;
+        }
+        Some(match Message::from_bytes(&msg) {
+            Ok(Message::P2PResponse(response)) => {
+                response.decode().and_then(P2PResponse::into_result)
+            }
+            Ok(msg) => Err(unexpected(&msg)),
+            Err(e) => Err(e),
+        })
+    }
+
+    // True, and one less to skip, if `msg` answers a request that timed
+    // out.
+    fn is_stale(&mut self, msg: &[u8]) -> bool {
+        let msg_type = match p2p_response_type(msg) {
+            Some(msg_type) => msg_type,
+            None => return false,
+        };
+        match self.stale.get_mut(&msg_type) {
+            Some(count) if *count > 0 => {
+                *count -= 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn timed_out(&mut self, expected: u16) {
+        *self.stale.entry(expected).or_insert(0) += 1;
+    }
+
+    // Stop waiting for an answer of type `expected`.
+    fn give_up(&mut self, expected: u16) -> Error {
+        self.timed_out(expected);
+        timed_out()
+    }
+}
+
+/*
+ * The steps of a call that takes more than one request, kept apart from
+ * the connection so that SyncClient and AsyncSyncClient run them the
+ * same way: make `next_request` and hand its answer to `receive` until
+ * there are none left, then `finish`. If a step fails, `abort` is sent
+ * without waiting for its answer.
+ */
+trait Exchange {
+    type Output;
+
+    fn next_request(&mut self) -> Result<Option<Message>, Error>;
+    fn receive(&mut self, answer: Message) -> Result<(), Error>;
+    fn finish(self) -> Result<Self::Output, Error>;
+
+    fn abort(&self) -> Option<Message> {
+        None
+    }
+}
+
+/// The type of the answer the node sends to `request`.
+pub fn response_type(request: &Message) -> Result<u16, Error> {
+    Ok(match request {
+        Message::Ping(_) => MSG_PING,
+        Message::GetHeaderByHash { .. } | Message::GetHeaderByHeight { .. } => MSG_HEADER,
+        Message::GetNSuccessors { .. } => MSG_HEADERHASHES,
+        Message::GetGeneration { .. } => MSG_GENERATION,
+        Message::GetBlockTxs { .. } => MSG_BLOCKTXS,
+        Message::TxPoolSyncInit => MSG_TXPOOLSYNCINIT,
+        Message::TxPoolSyncUnfold(_) => MSG_TXPOOLSYNCUNFOLD,
+        Message::TxPoolSyncGet(_) => MSG_TXPOOLSYNCGET,
+        Message::TxPoolSyncFinish { .. } => MSG_TXPOOLSYNCFINISH,
+        msg => bail!("Message type {} is not a request", msg.msg_type()),
+    })
+}
+
+// The response type field of a MSG_P2PRESPONSE, without decoding the rest.
+fn p2p_response_type(msg: &[u8]) -> Option<u16> {
+    if msg.len() < 2 || BigEndian::read_u16(&msg[0..2]) != MSG_P2PRESPONSE {
+        return None;
+    }
+    let msg_type = rlp::Rlp::new(&msg[2..]).val_at::<u64>(2).ok()?;
+    if msg_type > u64::from(u16::MAX) {
+        return None;
+    }
+    Some(msg_type as u16)
+}
+
+fn successors_request(hash: &[u8; 32], target_hash: &[u8; 32], n: u64) -> Message {
+    Message::GetNSuccessors { hash: hash.to_vec(), target_hash: target_hash.to_vec(), n }
+}
+
+fn header_response(msg: Message) -> Result<Header, Error> {
+    match msg {
+        Message::Header(header) => Ok(header),
+        msg => Err(unexpected(&msg)),
+    }
+}
+
+fn header_hashes_response(msg: Message) -> Result<Vec<(u64, Vec<u8>)>, Error> {
+    match msg {
+        Message::HeaderHashes(hashes) => Ok(hashes),
+        msg => Err(unexpected(&msg)),
+    }
+}
+
+fn generation_response(msg: Message) -> Result<Generation, Error> {
+    match msg {
+        Message::Generation { key_block, micro_blocks, forward } => {
+            Generation::new(key_block, micro_blocks, forward)
+        }
+        msg => Err(unexpected(&msg)),
+    }
+}
+
+fn block_txs_response(hash: &[u8; 32], msg: Message) -> Result<Vec<Vec<u8>>, Error> {
+    match msg {
+        Message::BlockTxs { hash: block, txs } if block[..] == hash[..] => Ok(txs),
+        Message::BlockTxs { .. } => bail!("Peer sent the transactions of another micro block"),
+        msg => Err(unexpected(&msg)),
+    }
+}
+
+// A light micro block made full, asking for the transactions we don't have
+// in one GetBlockTxs.
+struct ResolveMicroBlock<'a> {
+    micro_block: &'a MicroBlock,
+    txs: Vec<Vec<u8>>,
+    missing: Vec<Vec<u8>>,
+}
+
+impl<'a> ResolveMicroBlock<'a> {
+    fn new<F>(micro_block: &'a MicroBlock, known: F) -> ResolveMicroBlock<'a>
+    where
+        F: FnMut(&[u8]) -> Option<Vec<u8>>,
+    {
+        let (txs, missing) = if micro_block.is_light() {
+            split_known(micro_block, known)
+        } else {
+            (vec![], vec![])
+        };
+        ResolveMicroBlock { micro_block, txs, missing }
+    }
+}
+
+impl<'a> Exchange for ResolveMicroBlock<'a> {
+    type Output = MicroBlock;
+
+    fn next_request(&mut self) -> Result<Option<Message>, Error> {
+        if self.missing.is_empty() {
+            return Ok(None);
+        }
+        let tx_hashes = std::mem::take(&mut self.missing);
+        let hash = self.micro_block.header().hash().to_vec();
+        Ok(Some(Message::GetBlockTxs { hash, tx_hashes }))
+    }
+
+    fn receive(&mut self, answer: Message) -> Result<(), Error> {
+        let hash = self.micro_block.header().hash();
+        self.txs.extend(block_txs_response(&hash, answer)?);
+        Ok(())
+    }
+
+    fn finish(self) -> Result<MicroBlock, Error> {
+        if !self.micro_block.is_light() {
+            return Ok(self.micro_block.clone());
+        }
+        self.micro_block.with_txs(self.txs)
+    }
+}
+
+// The transactions of a light micro block we already have, and the hashes
+// of those we don't.
+fn split_known<F>(micro_block: &MicroBlock, mut known: F) -> (Vec<Vec<u8>>, Vec<Vec<u8>>)
+where
+    F: FnMut(&[u8]) -> Option<Vec<u8>>,
+{
+    let mut txs = vec![];
+    let mut missing = vec![];
+    for hash in micro_block.tx_hashes() {
+        match known(&hash) {
+            Some(tx) => txs.push(tx),
+            None => missing.push(hash),
+        }
+    }
+    (txs, missing)
+}
+
+/*
+ * Our side of unfolding a peer's tx pool tree, with nothing to compare it
+ * against: every node we send has an empty hash, so the peer unfolds it
+ * further, until only leaves are left. The answer doesn't say which node
+ * each child came from, so a child counts as one below the shallowest
+ * node of its batch; however wide the pool, a peer can't take us deeper
+ * than a tree of hashes goes.
+ */
+struct PoolWalk {
+    /// Nodes still to unfold, with their depth.
+    pending: Vec<(Unfold, usize)>,
+    /// Depth of the children of the last batch sent.
+    depth: usize,
+    /// Hashes of the leaves we don't know.
+    missing: Vec<Vec<u8>>,
+}
+
+impl PoolWalk {
+    fn new() -> PoolWalk {
+        PoolWalk { pending: vec![(Unfold::root(), 0)], depth: 0, missing: vec![] }
+    }
+
+    // The next batch of nodes to unfold, if any are left.
+    fn next_unfolds(&mut self) -> Result<Option<Vec<Unfold>>, Error> {
+        if self.pending.is_empty() {
+            return Ok(None);
+        }
+        let batch = self.pending.len().min(TX_POOL_SYNC_BATCH);
+        let (unfolds, depths): (Vec<_>, Vec<_>) = self.pending.drain(..batch).unzip();
+        self.depth = depths.into_iter().min().unwrap_or(0) + 1;
+        if self.depth > MAX_UNFOLD_DEPTH {
+            bail!("Peer's tx pool tree is deeper than any tree of hashes");
+        }
+        Ok(Some(unfolds))
+    }
+
+    fn receive<F>(&mut self, unfolds: Vec<Unfold>, known: &mut F)
+    where
+        F: FnMut(&[u8]) -> bool,
+    {
+        for unfold in unfolds {
+            match unfold {
+                Unfold::Node { path, .. } => {
+                    self.pending.push((Unfold::Node { path, hash: vec![] }, self.depth))
+                }
+                Unfold::Leaf { tx_hash } if !known(&tx_hash) => self.missing.push(tx_hash),
+                Unfold::Leaf { .. } => (),
+            }
+        }
+    }
+}
+
+/*
+ * A whole tx pool sync: init, unfold the peer's tree down to its leaves,
+ * get the transactions `known` doesn't have in batches and finish. Once
+ * the peer has taken its snapshot, giving up still finishes, not done.
+ */
+struct PoolSync<F> {
+    known: F,
+    walk: PoolWalk,
+    step: PoolStep,
+    txs: Vec<Vec<u8>>,
+}
+
+// The request to make next.
+enum PoolStep {
+    Init,
+    Unfold,
+    /// The next batch of missing transactions starts at this index.
+    Get(usize),
+    Finish,
+    Done,
+}
+
+impl<F> PoolSync<F>
+where
+    F: FnMut(&[u8]) -> bool,
+{
+    fn new(known: F) -> PoolSync<F> {
+        PoolSync { known, walk: PoolWalk::new(), step: PoolStep::Init, txs: vec![] }
+    }
+}
+
+impl<F> Exchange for PoolSync<F>
+where
+    F: FnMut(&[u8]) -> bool,
+{
+    type Output = Vec<Vec<u8>>;
+
+    fn next_request(&mut self) -> Result<Option<Message>, Error> {
+        loop {
+            match self.step {
+                PoolStep::Init => return Ok(Some(Message::TxPoolSyncInit)),
+                PoolStep::Unfold => match self.walk.next_unfolds()? {
+                    Some(unfolds) => return Ok(Some(unfold_request(&unfolds))),
+                    None => self.step = PoolStep::Get(0),
+                },
+                PoolStep::Get(start) if start < self.walk.missing.len() => {
+                    let end = self.walk.missing.len().min(start + TX_POOL_SYNC_BATCH);
+                    return Ok(Some(Message::TxPoolSyncGet(self.walk.missing[start..end].to_vec())));
+                }
+                PoolStep::Get(_) => self.step = PoolStep::Finish,
+                PoolStep::Finish => return Ok(Some(Message::TxPoolSyncFinish { done: true })),
+                PoolStep::Done => return Ok(None),
+            }
+        }
+    }
+
+    fn receive(&mut self, answer: Message) -> Result<(), Error> {
+        match self.step {
+            PoolStep::Init => {
+                init_response(answer)?;
+                self.step = PoolStep::Unfold;
+            }
+            PoolStep::Unfold => self.walk.receive(unfolds_response(answer)?, &mut self.known),
+            PoolStep::Get(start) => {
+                self.txs.extend(pool_txs_response(answer)?);
+                self.step = PoolStep::Get(start + TX_POOL_SYNC_BATCH);
+            }
+            PoolStep::Finish => {
+                finish_response(answer)?;
+                self.step = PoolStep::Done;
+            }
+            PoolStep::Done => return Err(unexpected(&answer)),
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Vec<Vec<u8>>, Error> {
+        Ok(self.txs)
+    }
+
+    fn abort(&self) -> Option<Message> {
+        match self.step {
+            PoolStep::Unfold | PoolStep::Get(_) => Some(Message::TxPoolSyncFinish { done: false }),
+            _ => None,
+        }
+    }
+}
+
+fn unfold_request(unfolds: &[Unfold]) -> Message {
+    Message::TxPoolSyncUnfold(unfolds.iter().map(Unfold::to_bytes).collect())
+}
+
+fn init_response(msg: Message) -> Result<(), Error> {
+    match msg {
+        Message::TxPoolSyncInit => Ok(()),
+        msg => Err(unexpected(&msg)),
+    }
+}
+
+fn unfolds_response(msg: Message) -> Result<Vec<Unfold>, Error> {
+    match msg {
+        Message::TxPoolSyncUnfold(unfolds) => {
+            unfolds.iter().map(|unfold| Unfold::from_bytes(unfold)).collect()
+        }
+        msg => Err(unexpected(&msg)),
+    }
+}
+
+fn pool_txs_response(msg: Message) -> Result<Vec<Vec<u8>>, Error> {
+    match msg {
+        Message::TxPoolSyncGet(txs) => Ok(txs),
+        msg => Err(unexpected(&msg)),
+    }
+}
+
+fn finish_response(msg: Message) -> Result<(), Error> {
+    match msg {
+        Message::TxPoolSyncFinish { .. } => Ok(()),
+        msg => Err(unexpected(&msg)),
+    }
+}
+
+fn unexpected(msg: &Message) -> Error {
+    Error::Invalid(format!("Unexpected answer of message type {}", msg.msg_type()))
+}
+
+fn is_timeout(e: &io::Error) -> bool {
+    e.kind() == io::ErrorKind::WouldBlock || e.kind() == io::ErrorKind::TimedOut
+}
+
+fn timed_out() -> Error {
+    Error::Transport(io::Error::new(io::ErrorKind::TimedOut, "Peer did not answer in time"))
+}
+
+fn closed() -> Error {
+    Error::Transport(io::Error::new(
+        io::ErrorKind::UnexpectedEof,
+        "Connection closed before the peer answered",
+    ))
+}
+
+// A peer answering the requests the tests below make, in order.
+#[cfg(test)]
+fn test_peer() -> (crate::aenode::Aenode, snow::Keypair, std::thread::JoinHandle<()>) {
+    use crate::keypair;
+    use crate::listener::PeerListener;
+
+    fn answer(conn: &mut PeerConnection, msg_type: u16, result: Result<Message, &str>) {
+        let response = P2PResponse::new(msg_type, result.map_err(String::from));
+        conn.send_message(&response.encode().unwrap()).unwrap();
+    }
+
+    let keys = keypair::generate().unwrap();
+    let public = keys.public.clone();
+    let listener = PeerListener::bind_with_prologue("127.0.0.1:0", keys, b"test".to_vec());
+    let listener = listener.unwrap();
+    let aenode = crate::aenode::Aenode {
+        pub_key: crate::rlp_val::to_base58check(&public),
+        address: listener.local_addr().unwrap().ip(),
+        port: listener.local_addr().unwrap().port(),
+    };
+    let peer = std::thread::spawn(move || {
+        let (mut conn, _) = listener.accept().unwrap();
+        let (key_block, micro) = test_headers();
+
+        assert_eq!(conn.recv().unwrap(), Some(Message::GetHeaderByHash { hash: vec![1; 32] }));
+        conn.send(&Message::KeyBlock(key_block.clone())).unwrap();
+        answer(&mut conn, MSG_HEADER, Ok(Message::Header(Header::Key(key_block.clone()))));
+
+        match conn.recv().unwrap() {
+            Some(Message::GetNSuccessors { n: 2, .. }) => (),
+            msg => panic!("Expected GetNSuccessors, got {:?}", msg),
+        }
+        let hashes = vec![(7, vec![7; 32]), (8, vec![8; 32])];
+        answer(&mut conn, MSG_HEADERHASHES, Ok(Message::HeaderHashes(hashes)));
+
+        match conn.recv().unwrap() {
+            Some(Message::GetHeaderByHeight { height: 9, .. }) => (),
+            msg => panic!("Expected GetHeaderByHeight, got {:?}", msg),
+        }
+        answer(&mut conn, MSG_HEADER, Err("Block not found"));
+
+        // Answer the first of these two only once the second is asked.
+        conn.recv().unwrap().unwrap();
+        conn.recv().unwrap().unwrap();
+        answer(&mut conn, MSG_HEADER, Ok(Message::Header(Header::Key(key_block))));
+        answer(&mut conn, MSG_HEADER, Ok(Message::Header(Header::Micro(micro))));
+
+        let (key_block, mut micro_blocks) = test_generation().into_blocks();
+        for _ in 0..2 {
+            match conn.recv().unwrap() {
+                Some(Message::GetGeneration { forward: true, .. }) => (),
+                msg => panic!("Expected GetGeneration, got {:?}", msg),
+            }
+            let generation = Message::Generation {
+                key_block: key_block.clone(),
+                micro_blocks: micro_blocks.clone(),
+                forward: true,
+            };
+            answer(&mut conn, MSG_GENERATION, Ok(generation));
+            micro_blocks.swap(0, 1);
+        }
+
+        let micro_block = &micro_blocks[1];
+        let request = Message::GetBlockTxs {
+            hash: micro_block.header().hash().to_vec(),
+            tx_hashes: vec![micro_block.tx_hashes()[1].clone()],
+        };
+        assert_eq!(conn.recv().unwrap(), Some(request));
+        let block_txs = Message::BlockTxs {
+            hash: micro_block.header().hash().to_vec(),
+            txs: vec![micro_block.txs()[1].clone()],
+        };
+        answer(&mut conn, MSG_BLOCKTXS, Ok(block_txs));
+
+        // A pool of three transactions, with hashes [1; 32], [2; 32] and
+        // [3; 32]; the last two under a node of their own.
+        let leaf = |n: u8| Unfold::Leaf { tx_hash: vec![n; 32] }.to_bytes();
+        let node = Unfold::Node { path: vec![0], hash: vec![9; 32] };
+        assert_eq!(conn.recv().unwrap(), Some(Message::TxPoolSyncInit));
+        answer(&mut conn, MSG_TXPOOLSYNCINIT, Ok(Message::TxPoolSyncInit));
+        assert_eq!(conn.recv().unwrap(), Some(unfold_request(&[Unfold::root()])));
+        let unfolds = Message::TxPoolSyncUnfold(vec![node.to_bytes(), leaf(1)]);
+        answer(&mut conn, MSG_TXPOOLSYNCUNFOLD, Ok(unfolds));
+        let node = Unfold::Node { path: vec![0], hash: vec![] };
+        assert_eq!(conn.recv().unwrap(), Some(unfold_request(&[node])));
+        let unfolds = Message::TxPoolSyncUnfold(vec![leaf(2), leaf(3)]);
+        answer(&mut conn, MSG_TXPOOLSYNCUNFOLD, Ok(unfolds));
+        let get = Message::TxPoolSyncGet(vec![vec![1; 32], vec![3; 32]]);
+        assert_eq!(conn.recv().unwrap(), Some(get));
+        let txs = Message::TxPoolSyncGet(vec![vec![1], vec![3]]);
+        answer(&mut conn, MSG_TXPOOLSYNCGET, Ok(txs));
+        assert_eq!(conn.recv().unwrap(), Some(Message::TxPoolSyncFinish { done: true }));
+        answer(&mut conn, MSG_TXPOOLSYNCFINISH, Ok(Message::TxPoolSyncFinish { done: true }));
+        conn.send(&Message::Close).unwrap();
+    });
+    (aenode, keypair::generate().unwrap(), peer)
+}
+
+#[cfg(test)]
+fn test_headers() -> (KeyBlock, MicroBlockHeader) {
+    let key_block = crate::messages::fixture_key_block();
+    let micro = MicroBlockHeader::new(1, key_block.height(), key_block.hash(), key_block.hash(),
+                                      [0; 32], [0; 32], key_block.time() + 1, None, [0; 64]);
+    (key_block, micro)
+}
+
+// The fixture key block and two micro blocks on it.
+#[cfg(test)]
+fn test_generation() -> Generation {
+    let (key_block, first) = test_headers();
+    let second = MicroBlockHeader::new(1, key_block.height(), first.hash(), key_block.hash(),
+                                       [0; 32], [1; 32], first.time() + 1, None, [0; 64]);
+    let micro_blocks = vec![
+        MicroBlock::new(first, vec![vec![1, 2, 3]], false),
+        MicroBlock::new(second, vec![vec![4], vec![5, 6]], false),
+    ];
+    Generation::new(key_block, micro_blocks, true).unwrap()
+}
+
+// The requests test_peer answers, made through `$client`, with `.await`
+// after each call for an AsyncSyncClient.
+#[cfg(test)]
+macro_rules! check_sync_client {
+    ($client:ident $(.$await:tt)?) => {{
+        let (key_block, micro) = test_headers();
+        let header = $client.get_header_by_hash(&[1; 32])$(.$await)?.unwrap();
+        assert_eq!(header, Header::Key(key_block.clone()));
+        let hashes = $client.get_n_successors(&[1; 32], &[2; 32], 2)$(.$await)?.unwrap();
+        assert_eq!(hashes, vec![(7, vec![7; 32]), (8, vec![8; 32])]);
+        match $client.get_header_by_height(9, &[2; 32])$(.$await)? {
+            Err(Error::Peer(reason)) => assert_eq!(reason, "Block not found"),
+            result => panic!("Expected the peer's error, got {:?}", result),
+        }
+        match $client.get_header_by_hash(&[3; 32])$(.$await)? {
+            Err(Error::Transport(e)) => assert_eq!(e.kind(), io::ErrorKind::TimedOut),
+            result => panic!("Expected a timeout, got {:?}", result),
+        }
+        let header = $client.get_header_by_hash(&[4; 32])$(.$await)?.unwrap();
+        assert_eq!(header, Header::Micro(micro));
+
+        let generation = $client.get_generation(&key_block.hash(), true)$(.$await)?.unwrap();
+        assert_eq!(generation, test_generation());
+        assert_eq!(generation.headers().len(), 3);
+        assert!($client.get_generation(&key_block.hash(), true)$(.$await)?.is_err());
+
+        let full = &generation.micro_blocks()[1];
+        let light = MicroBlock::new(full.header().clone(), full.tx_hashes(), true);
+        let (first_hash, first) = (full.tx_hashes()[0].clone(), full.txs()[0].clone());
+        let known = |hash: &[u8]| if hash == first_hash { Some(first.clone()) } else { None };
+        assert_eq!(&$client.resolve_micro_block(&light, known)$(.$await)?.unwrap(), full);
+
+        let txs = $client.sync_tx_pool(|hash| hash == &[2; 32][..])$(.$await)?.unwrap();
+        assert_eq!(txs, vec![vec![1], vec![3]]);
+
+        assert_eq!($client.recv()$(.$await)?.unwrap(), Some(Message::KeyBlock(key_block)));
+        assert_eq!($client.recv()$(.$await)?.unwrap(), Some(Message::Close));
+    }};
+}
+
+#[test]
+fn test_sync_client() {
+    let (aenode, keys, peer) = test_peer();
+    let conn = PeerConnection::connect_with_prologue(&aenode, &keys, b"test").unwrap();
+    let mut client = SyncClient::with_timeout(conn, Duration::from_millis(300));
+
+    let read_timeout = Some(Duration::from_secs(5));
+    client.connection().set_read_timeout(read_timeout).unwrap();
+    check_sync_client!(client);
+    assert_eq!(client.connection().read_timeout().unwrap(), read_timeout);
+    assert!(response_type(&Message::Close).is_err());
+    peer.join().unwrap();
+}
+
+#[cfg(feature = "async")]
+#[tokio::test]
+async fn test_async_sync_client() {
+    let (aenode, keys, peer) = test_peer();
+    let conn = AsyncPeerConnection::connect_with_prologue(&aenode, &keys, b"test").await;
+    let mut client = AsyncSyncClient::with_timeout(conn.unwrap(), Duration::from_millis(300));
+
+    check_sync_client!(client.await);
+    tokio::task::spawn_blocking(move || peer.join().unwrap()).await.unwrap();
+}
+
+// Unfolds the tx pool tree `children` describes, as a peer would, and
+// counts the unfold requests it took.
+#[cfg(test)]
+fn walk_pool<C>(children: C) -> Result<(Vec<Vec<u8>>, usize), Error>
+where
+    C: Fn(&[u8]) -> Vec<Unfold>,
+{
+    let mut missing = vec![];
+    let mut sync = PoolSync::new(|hash: &[u8]| {
+        missing.push(hash.to_vec());
+        false
+    });
+    let mut rounds = 0;
+    loop {
+        match sync.next_request()? {
+            Some(Message::TxPoolSyncUnfold(unfolds)) => {
+                rounds += 1;
+                let answer = unfolds.iter().flat_map(|unfold| match Unfold::from_bytes(unfold) {
+                    Ok(Unfold::Node { path, .. }) => children(&path),
+                    _ => panic!("Unfolding a leaf"),
+                });
+                sync.receive(Message::TxPoolSyncUnfold(answer.map(|u| u.to_bytes()).collect()))?;
+            }
+            Some(Message::TxPoolSyncInit) => sync.receive(Message::TxPoolSyncInit)?,
+            Some(_) => break,
+            None => unreachable!(),
+        }
+    }
+    drop(sync);
+    Ok((missing, rounds))
+}
+
+#[test]
+fn test_pool_walk_depth() {
+    // 16 nodes under each of the top two levels, 2 under each of the 4096
+    // nodes below them and a leaf under each of those: 128 unfold rounds
+    // for a tree five deep.
+    let node = |path: Vec<u8>| Unfold::Node { path, hash: vec![9; 32] };
+    let wide = |path: &[u8]| -> Vec<Unfold> {
+        let width = match path.len() {
+            0..=2 => 16,
+            3 => 2,
+            _ => return vec![Unfold::Leaf { tx_hash: [path, &[0; 28]].concat() }],
+        };
+        (0..width).map(|n| node([path, &[n]].concat())).collect()
+    };
+    let (missing, rounds) = walk_pool(wide).unwrap();
+    assert_eq!(missing.len(), 8192);
+    assert_eq!(rounds, 1 + 1 + 3 + 41 + 82);
+
+    // A node under every node is deeper than a tree of hashes goes.
+    let deep = |path: &[u8]| vec![node([path, &[0]].concat())];
+    assert!(walk_pool(deep).is_err());
+}